@@ -25,7 +25,7 @@ fn parse_file(filename: String) -> Result<bool, Box<dyn Error>> {
             return Err(Box::new(err))
         }
     };
-    let mut mvd = Mvd::new(*buffer,
+    let mut mvd = Mvd::new(buffer.as_slice(),
 #[cfg(feature = "ascii_strings")]
                            None,
 #[cfg(feature = "trace")]
@@ -50,7 +50,7 @@ fn parse_file(filename: String) -> Result<bool, Box<dyn Error>> {
         //println!("--- frame {}:{} ---", frame.frame, frame.time);
         // if you need to keep the last state
         // let old_state = state.clone();
-        state.apply_messages_mvd(&frame.messages, frame.last);
+        state.apply_messages_mvd(&frame.messages, frame.last)?;
         // get the players when intermission is reached
         for message in frame.messages {
             match message {
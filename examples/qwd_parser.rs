@@ -0,0 +1,62 @@
+use std::io::Read;
+use std::error::Error;
+use std::fs::File;
+use std::env;
+use quakeworld::qwd::Qwd;
+use quakeworld::protocol::types::ServerMessage;
+
+#[cfg(feature = "trace")]
+use quakeworld::utils::trace::*;
+
+// the most basic implementation of a qwd parser
+fn parse_file(filename: String) -> Result<bool, Box<dyn Error>> {
+    // read the file into a buffer
+    let mut buffer = Vec::new();
+    let mut file = File::open(filename)?;
+    file.read_to_end(&mut buffer)?;
+    let mut qwd = Qwd::new(buffer,
+#[cfg(feature = "ascii_strings")]
+                           None,
+#[cfg(feature = "trace")]
+                           false,
+                           )?;
+
+    while !qwd.finished {
+        let frame = match qwd.parse_frame() {
+            Ok(v) => v,
+            Err(e) => {
+                #[cfg(feature = "trace")]
+                print_message_trace(&qwd.message, false, 0, 2, false)?;
+                return Err(Box::new(e));
+            },
+        };
+        // the usercommands the client sent
+        if let Some(command) = frame.command() {
+            println!("{}: {:?}", frame.time, command);
+        }
+        // and what the server sent
+        for message in frame.messages() {
+            if let ServerMessage::Print(print) = message {
+                print!("{}", print.message.string);
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        println!("need to supply a demo");
+        return
+    }
+    let filename = &args[1];
+    match parse_file(filename.to_string()) {
+        Ok(..) => {
+            println!("{} parsed.", filename);
+        }
+        Err(err) => {
+            eprintln!("error in file {}: {}", filename, err);
+        }
+    }
+}
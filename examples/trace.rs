@@ -3,7 +3,7 @@ use quakeworld::protocol::types::{ServerClient, ServerMessage};
 use quakeworld::utils::trace::print_message_trace;
 fn main() {
         let b: Vec<u8> = vec![8, 2, 0x68, 0x65, 0x6c, 0x6c, 0x6f,0x0];
-        let mut message = Message::new(Box::new(b.clone()), 0, b.len(), false, MessageFlags {..Default::default()}, None, MessageType::Connection);
+        let mut message = Message::new(b.as_slice(), 0, b.len(), false, MessageFlags {..Default::default()}, None, MessageType::Connection);
         message.trace.enabled = true;
         let msg_cmd = match message.read_u8(false) {
             Ok(cmd) => cmd,
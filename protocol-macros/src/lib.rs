@@ -1,6 +1,7 @@
 use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
 use quote::{quote, format_ident};
-use syn::{Data, DataStruct, Fields};
+use syn::{Data, DataStruct, Fields, GenericArgument, Lit, Meta, NestedMeta, PathArguments, Type};
 
 /*
 #[proc_macro]
@@ -13,7 +14,26 @@ pub fn to_value(_item: TokenStream) -> TokenStream {
 }
 */
 
-#[proc_macro_derive(ParseMessage)]
+/// reads and writes the fields of a struct in order, the function used is picked from the
+/// lowercased type name (`read_u8`/`write_u8` for `u8`, `Option<u8>` uses the same)
+///
+/// the behaviour can be changed per field with `#[qw(...)]`:
+/// * `#[qw(angle16)]` - any other name replaces the type name, here `read_angle16`/`write_angle16`
+/// * `#[qw(if = "expr")]` - only read/write the field if expr is true, otherwise its `None` or the
+///   default, expr can use the fields read before
+/// * `#[qw(try_from = "u8")]` - read/write a `u8` and convert it with `TryFrom`/`as`
+/// * `#[qw(bits = "u16")]` - read/write a `u16` as the bits of a bitflags type
+/// * `#[qw(value = "expr")]` - field isnt read/written but computed from the fields before
+/// * `#[qw(coord_from_flags = "x, y, z")]` - read each component of a [`CoordinateVectorOption`]
+///   only if the matching expression is true, `angle_from_flags` does the same for [`AngleVectorOption`]
+///
+/// fields used in expressions need to be `Copy`
+///
+/// and per struct:
+/// * `#[qw(order = "a, b, c")]` - read/write the fields in this order instead of the declared one
+/// * `#[qw(nested)]` - `read` returns the struct instead of a `ServerMessage`, for structs that are
+///   part of another message
+#[proc_macro_derive(ParseMessage, attributes(qw))]
 pub fn parse_message_derive(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -23,49 +43,383 @@ pub fn parse_message_derive(input: TokenStream) -> TokenStream {
     impl_parsemessage_macro(&ast)
 }
 
+#[derive(Default)]
+struct FieldAttributes {
+    function: Option<String>,
+    condition: Option<TokenStream2>,
+    try_from: Option<String>,
+    bits: Option<String>,
+    value: Option<TokenStream2>,
+    from_flags: Option<Vec<TokenStream2>>,
+}
+
+fn parse_expression(s: &str) -> TokenStream2 {
+    match s.parse::<TokenStream2>() {
+        Ok(t) => t,
+        Err(e) => panic!("could not parse expression \"{}\": {}", s, e),
+    }
+}
+
+fn field_attributes(field: &syn::Field) -> FieldAttributes {
+    let mut attributes = FieldAttributes::default();
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("qw")) {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => panic!("expected #[qw(...)]"),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) => {
+                    let name = quote! { #path }.to_string();
+                    attributes.function = Some(name);
+                },
+                NestedMeta::Meta(Meta::NameValue(nv)) => {
+                    let path = &nv.path;
+                    let name = quote! { #path }.to_string();
+                    let value = match nv.lit {
+                        Lit::Str(s) => s.value(),
+                        _ => panic!("expected a string for {}", name),
+                    };
+                    match name.as_str() {
+                        "if" => attributes.condition = Some(parse_expression(&value)),
+                        "try_from" => attributes.try_from = Some(value),
+                        "bits" => attributes.bits = Some(value),
+                        "value" => attributes.value = Some(parse_expression(&value)),
+                        "coord_from_flags" | "angle_from_flags" => {
+                            let expressions: Vec<TokenStream2> = value.split(',').map(parse_expression).collect();
+                            if expressions.len() != 3 {
+                                panic!("{} needs 3 expressions", name);
+                            }
+                            attributes.from_flags = Some(expressions);
+                        },
+                        _ => panic!("unknown qw attribute {}", name),
+                    }
+                },
+                _ => panic!("unknown qw attribute"),
+            }
+        }
+    }
+    attributes
+}
+
+#[derive(Default)]
+struct StructAttributes {
+    order: Option<Vec<String>>,
+    nested: bool,
+}
+
+fn struct_attributes(ast: &syn::DeriveInput) -> StructAttributes {
+    let mut attributes = StructAttributes::default();
+    for attr in ast.attrs.iter().filter(|a| a.path.is_ident("qw")) {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list,
+            _ => panic!("expected #[qw(...)]"),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("nested") => attributes.nested = true,
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("order") => {
+                    let value = match nv.lit {
+                        Lit::Str(s) => s.value(),
+                        _ => panic!("expected a string for order"),
+                    };
+                    attributes.order = Some(value.split(',').map(|name| name.trim().to_string()).collect());
+                },
+                _ => panic!("unknown qw struct attribute"),
+            }
+        }
+    }
+    attributes
+}
+
+/// returns the type inside of an `Option` if it is one
+fn option_inner(ty: &Type) -> Option<&Type> {
+    if let Type::Path(path) = ty {
+        let segment = path.path.segments.last()?;
+        if segment.ident != "Option" {
+            return None;
+        }
+        if let PathArguments::AngleBracketed(args) = &segment.arguments {
+            if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                return Some(inner);
+            }
+        }
+    }
+    None
+}
+
+/// the lowercased name of the type without its generics, `StringByte<'a>` is `stringbyte`
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path.path.segments.last().unwrap().ident.to_string().to_lowercase(),
+        _ => quote! { #ty }.to_string().to_lowercase(),
+    }
+}
+
+fn is_primitive(name: &str) -> bool {
+    matches!(name, "u8" | "u16" | "u32" | "i8" | "i16" | "i32" | "f32" | "angle" | "angle16" | "coordinate")
+}
+
+fn collect_idents(tokens: &TokenStream2, idents: &mut Vec<String>) {
+    for token in tokens.clone() {
+        match token {
+            TokenTree::Ident(ident) => idents.push(ident.to_string()),
+            TokenTree::Group(group) => collect_idents(&group.stream(), idents),
+            _ => {},
+        }
+    }
+}
+
 fn impl_parsemessage_macro(ast: &syn::DeriveInput) -> TokenStream {
     let fields = match &ast.data {
         Data::Struct(DataStruct { fields: Fields::Named(fields), .. }) => &fields.named,
         _ => panic!("expected a struct with named fields"),
     };
+    let struct_attributes = struct_attributes(ast);
+
+    // the order the fields are read and written in
+    let ordered: Vec<&syn::Field> = match &struct_attributes.order {
+        Some(order) => {
+            if order.len() != fields.len() {
+                panic!("order needs to list all {} fields", fields.len());
+            }
+            order.iter().map(|name| {
+                match fields.iter().find(|field| field.ident.as_ref().unwrap() == name) {
+                    Some(field) => field,
+                    None => panic!("order lists unknown field {}", name),
+                }
+            }).collect()
+        },
+        None => fields.iter().collect(),
+    };
+
+    // fields used in expressions are bound under their own name, values are only computed when reading
+    let mut referenced = vec![];
+    let mut referenced_write = vec![];
+    for field in fields {
+        let attributes = field_attributes(field);
+        let expressions = attributes.condition.iter()
+            .chain(attributes.from_flags.iter().flatten());
+        for expression in expressions {
+            collect_idents(expression, &mut referenced_write);
+        }
+        if let Some(value) = &attributes.value {
+            collect_idents(value, &mut referenced);
+        }
+    }
+    referenced.extend(referenced_write.iter().cloned());
+
+    let mut field_read = vec![];
+    let mut field_write = vec![];
+    for field in ordered {
+        let field_name = field.ident.as_ref().unwrap();
+        let name = &format_ident!("{}_value", field_name.to_string().trim_start_matches("r#"));
+        let annotation = field_name.to_string();
+        let annotation_name = annotation.trim_start_matches("r#").to_string();
+        let attributes = field_attributes(field);
+        let alias = if referenced.contains(&annotation) {
+            quote! { let #field_name = #name; }
+        } else {
+            quote! {}
+        };
+        let inner = option_inner(&field.ty);
+        let is_option = inner.is_some();
+        let ty = inner.unwrap_or(&field.ty);
+        let type_name = type_name(ty);
+
+        if let Some(value) = &attributes.value {
+            field_read.push(quote! {
+                let #name = #value;
+                #alias
+            });
+            continue;
+        }
+
+        if let Some(from_flags) = &attributes.from_flags {
+            let vector_type = if is_option { ty } else { &field.ty };
+            let component = attributes.function.clone().unwrap_or_else(|| {
+                if type_name.starts_with("angle") { "angle".to_string() } else { "coordinate".to_string() }
+            });
+            let read_function = format_ident!("read_{}", component);
+            let write_function = format_ident!("write_{}", component);
+            let (fx, fy, fz) = (&from_flags[0], &from_flags[1], &from_flags[2]);
+            let vector = quote! {
+                {
+                    let mut v = #vector_type{ x: None, y: None, z: None };
+                    if #fx {
+                        trace_annotate!(message, "x");
+                        v.x = Some(message.#read_function(false)?);
+                    }
+                    if #fy {
+                        trace_annotate!(message, "y");
+                        v.y = Some(message.#read_function(false)?);
+                    }
+                    if #fz {
+                        trace_annotate!(message, "z");
+                        v.z = Some(message.#read_function(false)?);
+                    }
+                    v
+                }
+            };
+            if is_option {
+                field_read.push(quote! {
+                    let #name = #vector;
+                    let #name = if #name.x.is_none() && #name.y.is_none() && #name.z.is_none() { None } else { Some(#name) };
+                    #alias
+                });
+                field_write.push(quote! {
+                    let v = self.#field_name.unwrap_or(#vector_type{ x: None, y: None, z: None });
+                });
+            } else {
+                field_read.push(quote! {
+                    let #name = #vector;
+                    #alias
+                });
+                field_write.push(quote! {
+                    let v = self.#field_name;
+                });
+            }
+            field_write.push(quote! {
+                if #fx {
+                    size += message.#write_function(v.x.unwrap_or_default());
+                }
+                if #fy {
+                    size += message.#write_function(v.y.unwrap_or_default());
+                }
+                if #fz {
+                    size += message.#write_function(v.z.unwrap_or_default());
+                }
+            });
+            continue;
+        }
+
+        let function_name = attributes.try_from.clone()
+            .or_else(|| attributes.bits.clone())
+            .or_else(|| attributes.function.clone())
+            .unwrap_or_else(|| type_name.clone());
+        let read_function = format_ident!("read_{}", function_name);
+        let write_function = format_ident!("write_{}", function_name);
+
+        let read = match (&attributes.try_from, &attributes.bits) {
+            (Some(_), _) => quote! { <#ty>::try_from(message.#read_function(false)?)? },
+            (None, Some(_)) => quote! { <#ty>::from_bits_truncate(message.#read_function(false)?) },
+            (None, None) => quote! { message.#read_function(false)? },
+        };
+        let value = match (&attributes.try_from, is_option) {
+            (None, _) if attributes.bits.is_some() => {
+                if is_option {
+                    quote! { self.#field_name.unwrap_or_default().bits() }
+                } else {
+                    quote! { self.#field_name.bits() }
+                }
+            },
+            (Some(from), _) => {
+                let from = format_ident!("{}", from);
+                if is_option {
+                    quote! { (self.#field_name.unwrap_or_default() as #from) }
+                } else {
+                    quote! { (self.#field_name as #from) }
+                }
+            },
+            (None, true) => {
+                if is_primitive(&function_name) {
+                    quote! { self.#field_name.unwrap_or_default() }
+                } else {
+                    quote! { &self.#field_name.clone().unwrap_or_default() }
+                }
+            },
+            (None, false) => {
+                if is_primitive(&function_name) {
+                    quote! { self.#field_name }
+                } else {
+                    quote! { &self.#field_name }
+                }
+            },
+        };
+        let write = quote! {
+            size += message.#write_function(#value);
+        };
+
+        match &attributes.condition {
+            Some(condition) => {
+                let read_value = if is_option { quote! { Some(#read) } } else { read };
+                let default_value = if is_option { quote! { None } } else { quote! { Default::default() } };
+                field_read.push(quote! {
+                    let #name = if #condition {
+                        trace_annotate!(message, #annotation_name);
+                        #read_value
+                    } else {
+                        #default_value
+                    };
+                    #alias
+                });
+                field_write.push(quote! {
+                    if #condition {
+                        #write
+                    }
+                });
+            },
+            None => {
+                let read_value = if is_option { quote! { Some(#read) } } else { read };
+                field_read.push(quote! {
+                    trace_annotate!(message, #annotation_name);
+                    let #name = #read_value;
+                    #alias
+                });
+                field_write.push(write);
+            },
+        }
+    }
+
+    // the writer needs the same names
+    let field_bind = fields.iter()
+        .filter_map(|field| field.ident.as_ref())
+        .filter(|name| referenced_write.contains(&name.to_string()))
+        .map(|name| quote! { let #name = self.#name; });
+
     let field_name = fields.iter().map(|field| &field.ident);
-    let field_name_annotate = fields.iter().map(|field| {
-        let ft = &field.ident;
-        let q = quote! { #ft };
-        format!("{}", q.to_string())
-    });
     let field_name_value = fields.iter().map(|field| {
-        let ft = &field.ident;
-        let q = quote! { #ft };
-        format_ident!("{}_value", q.to_string())
-    });
-    let field_name_value1 = fields.iter().map(|field| {
-        let ft = &field.ident;
-        let q = quote! { #ft };
-        format_ident!("{}_value", q.to_string())
-    });
-
-    let field_function = fields.iter().map(|field| {
-        let ft = &field.ty;
-        let q = quote! { #ft };
-        format_ident!("read_{}", q.to_string().to_lowercase())
+        let name = field.ident.as_ref().unwrap().to_string();
+        format_ident!("{}_value", name.trim_start_matches("r#"))
     });
-
     let struct_name = &ast.ident;
+    // structs holding strings borrow them from the message
+    let (impl_generics, ty_generics, _) = ast.generics.split_for_impl();
+    let (read_generics, lifetime) = match ast.generics.lifetimes().next() {
+        Some(lifetime) => (quote! {}, lifetime.lifetime.clone()),
+        None => (quote! { <'a> }, syn::Lifetime::new("'a", proc_macro2::Span::call_site())),
+    };
 
-    let gen = quote! {
-        impl #struct_name {
-            fn read(message: &mut Message) -> Result<ServerMessage, MessageError>
+    let read = if struct_attributes.nested {
+        quote! {
+            pub fn read #read_generics (message: &mut Message<#lifetime>) -> Result<#struct_name #ty_generics, MessageError>
             {
                 trace_start!(message, false);
                 #(
-                trace_annotate!(message, #field_name_annotate);
-                let #field_name_value = message.#field_function(false)?;
+                    #field_read
+                 )*
+                let v = #struct_name{
+                    #(
+                        #field_name: #field_name_value,
+                        )*
+                };
+
+                trace_stop!(message);
+                Ok(v)
+            }
+        }
+    } else {
+        quote! {
+            fn read #read_generics (message: &mut Message<#lifetime>) -> Result<ServerMessage<#lifetime>, MessageError>
+            {
+                trace_start!(message, false);
+                #(
+                    #field_read
                  )*
                 let v = ServerMessage::#struct_name(
                         #struct_name{
                             #(
-                                #field_name: #field_name_value1,
+                                #field_name: #field_name_value,
                                 )*
                         });
 
@@ -74,6 +428,24 @@ fn impl_parsemessage_macro(ast: &syn::DeriveInput) -> TokenStream {
             }
         }
     };
+
+    let gen = quote! {
+        impl #impl_generics #struct_name #ty_generics {
+            #read
+
+            #[allow(unused_mut, clippy::nonminimal_bool)]
+            pub fn write(&self, message: &mut Message) -> Result<usize, MessageError>
+            {
+                let mut size: usize = 0;
+                #(
+                    #field_bind
+                 )*
+                #(
+                    #field_write
+                 )*
+                Ok(size)
+            }
+        }
+    };
     gen.into()
 }
-
@@ -1,8 +1,7 @@
 
 mod checksum_table;
 
-pub fn generate_checksum(buffer: impl Into<Vec<u8>>, start: usize, stop: usize, sequence: u32) -> u16 {
-    let buffer = buffer.into();
+pub fn generate_checksum(buffer: &[u8], start: usize, stop: usize, sequence: u32) -> u16 {
     let mut chkb: [u8; 64] = [0;64];
     let mut length = stop - start;
     if length > 60 {
@@ -20,13 +19,12 @@ pub fn generate_checksum(buffer: impl Into<Vec<u8>>, start: usize, stop: usize,
     chkb[length + 3] = check_table[p + 3];
     length += 4;
 
-    block(chkb, length)
+    block(&chkb, length)
 }
 
-pub fn block(buffer: impl Into<Vec<u8>>, length: usize) -> u16 {
+pub fn block(buffer: &[u8], length: usize) -> u16 {
     let cst = &checksum_table::CHECKSUM_TABLE_U16;
     let mut crc: u16 = 0xffff;
-    let buffer = buffer.into();
     for b in buffer.iter().take(length) {
         crc = (crc << 8) ^ cst[((crc >> 8) as u8 ^ b) as usize];
     }
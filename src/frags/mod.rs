@@ -0,0 +1,314 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::protocol::types::{Print, ServerMessage};
+use crate::state::State;
+
+/// partial rule set covering the obituaries of the stock qw progs and common ktx ones,
+/// it is not the complete fragfile.dat shipped with ezquake
+pub static DEFAULT_FRAGFILE: &str = include_str!("fragfile.dat");
+
+// PRINT_CHAT, so players can't fake obituaries
+const PRINT_CHAT: u8 = 3;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FragFileError {
+    #[error("line {0}: unterminated quote")]
+    UnterminatedQuote(usize),
+    #[error("line {0}: missing arguments")]
+    MissingArguments(usize),
+    #[error("line {0}: unknown obituary type {1}")]
+    UnknownObituaryType(usize, String),
+    #[error("line {0}: unknown weapon class {1}")]
+    UnknownWeaponClass(usize, String),
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObituaryType {
+    PlayerDeath,
+    PlayerSuicide,
+    XFragsUnknown,
+    XTeamkillsUnknown,
+    XTeamkilledUnknown,
+    XFraggedByY,
+    XFragsY,
+    XTeamkillsY,
+    XTeamkilledByY,
+}
+
+impl ObituaryType {
+    fn from_keyword(keyword: &str) -> Option<ObituaryType> {
+        Some(match keyword {
+            "PLAYER_DEATH" => ObituaryType::PlayerDeath,
+            "PLAYER_SUICIDE" => ObituaryType::PlayerSuicide,
+            "X_FRAGS_UNKNOWN" => ObituaryType::XFragsUnknown,
+            "X_TEAMKILLS_UNKNOWN" => ObituaryType::XTeamkillsUnknown,
+            "X_TEAMKILLED_UNKNOWN" => ObituaryType::XTeamkilledUnknown,
+            "X_FRAGGED_BY_Y" => ObituaryType::XFraggedByY,
+            "X_FRAGS_Y" => ObituaryType::XFragsY,
+            "X_TEAMKILLS_Y" => ObituaryType::XTeamkillsY,
+            "X_TEAMKILLED_BY_Y" => ObituaryType::XTeamkilledByY,
+            _ => return None,
+        })
+    }
+
+    /// the message names a second player
+    fn has_second_player(&self) -> bool {
+        matches!(self, ObituaryType::XFraggedByY | ObituaryType::XFragsY |
+            ObituaryType::XTeamkillsY | ObituaryType::XTeamkilledByY)
+    }
+
+    fn kind(&self) -> FragKind {
+        match self {
+            ObituaryType::PlayerDeath => FragKind::Environment,
+            ObituaryType::PlayerSuicide => FragKind::Suicide,
+            ObituaryType::XFragsUnknown | ObituaryType::XFraggedByY | ObituaryType::XFragsY => FragKind::Frag,
+            ObituaryType::XTeamkillsUnknown | ObituaryType::XTeamkilledUnknown |
+                ObituaryType::XTeamkillsY | ObituaryType::XTeamkilledByY => FragKind::Teamkill,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct WeaponClass {
+    pub keyword: String,
+    pub name: String,
+}
+
+/// `<first><message1>` or `<first><message1><second><message2>`
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Obituary {
+    pub obituary_type: ObituaryType,
+    pub weapon: String,
+    pub message1: Vec<u8>,
+    pub message2: Vec<u8>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FragKind {
+    Frag,
+    Suicide,
+    Teamkill,
+    Environment,
+}
+
+/// killer and victim are player numbers, unknown when the message doesn't name them
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct FragEvent {
+    pub killer: Option<u16>,
+    pub victim: Option<u16>,
+    /// keyword of the [`WeaponClass`]
+    pub weapon: String,
+    pub kind: FragKind,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FragRules {
+    pub weapon_classes: Vec<WeaponClass>,
+    pub obituaries: Vec<Obituary>,
+}
+
+/// splits a line on whitespace, double quoted arguments keep their whitespace
+fn tokenize(line_number: usize, line: &str) -> Result<Vec<String>, FragFileError> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut token = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err(FragFileError::UnterminatedQuote(line_number)),
+                }
+            }
+        } else {
+            token.push(c);
+            while let Some(c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(*c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+impl FragRules {
+    /// reads a fragfile.dat, only weapon classes and obituaries are used
+    pub fn parse(fragfile: &str) -> Result<FragRules, FragFileError> {
+        let mut rules = FragRules::default();
+        for (i, line) in fragfile.lines().enumerate() {
+            let line_number = i + 1;
+            let tokens = tokenize(line_number, line)?;
+            if tokens.len() < 2 || !tokens[0].eq_ignore_ascii_case("#DEFINE") {
+                continue;
+            }
+            match tokens[1].to_uppercase().as_str() {
+                "WEAPON_CLASS" | "WC" => {
+                    if tokens.len() < 4 {
+                        return Err(FragFileError::MissingArguments(line_number));
+                    }
+                    rules.weapon_classes.push(WeaponClass{
+                        keyword: tokens[2].clone(),
+                        name: tokens[3].clone(),
+                    });
+                },
+                "OBITUARY" | "OBIT" => {
+                    if tokens.len() < 5 {
+                        return Err(FragFileError::MissingArguments(line_number));
+                    }
+                    let obituary_type = match ObituaryType::from_keyword(&tokens[2].to_uppercase()) {
+                        Some(obituary_type) => obituary_type,
+                        None => return Err(FragFileError::UnknownObituaryType(line_number, tokens[2].clone())),
+                    };
+                    if !rules.weapon_classes.iter().any(|weapon| weapon.keyword == tokens[3]) {
+                        return Err(FragFileError::UnknownWeaponClass(line_number, tokens[3].clone()));
+                    }
+                    rules.obituaries.push(Obituary{
+                        obituary_type,
+                        weapon: tokens[3].clone(),
+                        message1: tokens[4].clone().into_bytes(),
+                        message2: tokens.get(5).cloned().unwrap_or_default().into_bytes(),
+                    });
+                },
+                _ => {},
+            }
+        }
+        Ok(rules)
+    }
+
+    /// the rules of [`DEFAULT_FRAGFILE`]
+    pub fn stock() -> FragRules {
+        FragRules::parse(DEFAULT_FRAGFILE).unwrap()
+    }
+
+    /// matches a [`Print`] against the names of the players in `state`, longest names first
+    pub fn detect(&self, state: &State, print: &Print) -> Option<FragEvent> {
+        if print.from == PRINT_CHAT {
+            return None;
+        }
+        let text = print.message.bytes.strip_suffix(b"\n").unwrap_or(&print.message.bytes);
+        let mut players: Vec<(u16, &[u8])> = state.players.iter()
+            .filter(|(_, player)| !player.spectator && !player.name.bytes.is_empty())
+            .map(|(player_number, player)| (*player_number, player.name.bytes.as_ref()))
+            .collect();
+        // longest names first so a name that is a prefix of another one can't take its frags
+        players.sort_by(|(a_number, a_name), (b_number, b_name)| {
+            b_name.len().cmp(&a_name.len()).then(a_number.cmp(b_number))
+        });
+
+        for (first, name) in &players {
+            for obituary in &self.obituaries {
+                let rest = match text.strip_prefix(*name).and_then(|rest| rest.strip_prefix(obituary.message1.as_slice())) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                let second = if obituary.obituary_type.has_second_player() {
+                    let second = players.iter().find(|(_, name)| {
+                        rest.strip_prefix(*name) == Some(obituary.message2.as_slice())
+                    });
+                    match second {
+                        Some((second, _)) => Some(*second),
+                        None => continue,
+                    }
+                } else if rest.is_empty() {
+                    None
+                } else {
+                    continue;
+                };
+                let first = Some(*first);
+                let (killer, victim) = match obituary.obituary_type {
+                    ObituaryType::PlayerDeath | ObituaryType::XTeamkilledUnknown => (None, first),
+                    ObituaryType::PlayerSuicide => (first, first),
+                    ObituaryType::XFragsUnknown | ObituaryType::XTeamkillsUnknown => (first, None),
+                    ObituaryType::XFraggedByY | ObituaryType::XTeamkilledByY => (second, first),
+                    ObituaryType::XFragsY | ObituaryType::XTeamkillsY => (first, second),
+                };
+                return Some(FragEvent{
+                    killer,
+                    victim,
+                    weapon: obituary.weapon.clone(),
+                    kind: obituary.obituary_type.kind(),
+                });
+            }
+        }
+        None
+    }
+
+    /// [`FragRules::detect`] for every [`ServerMessage::Print`] in `messages`
+    pub fn detect_messages(&self, state: &State, messages: &[ServerMessage]) -> Vec<FragEvent> {
+        messages.iter()
+            .filter_map(|message| match message {
+                ServerMessage::Print(print) => self.detect(state, print),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frags::*;
+    use crate::mvd::MvdTarget;
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    #[test]
+    fn obituaries() {
+        let ascii_converter = AsciiConverter::new();
+        let mut state = State::new();
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 0, uid: 1, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice") }),
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 1, uid: 2, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice2") }),
+        ], MvdTarget{ to: 0, command: DemoCommand::All }).unwrap();
+        let print = |from, text: &str| Print{ from, message: ascii_converter.convert_to_stringbyte(text) };
+
+        let rules = FragRules::stock();
+        assert_eq!(rules.detect(&state, &print(1, "alice rides alice2's rocket\n")), Some(FragEvent{
+            killer: Some(1), victim: Some(0), weapon: "RL".to_string(), kind: FragKind::Frag,
+        }));
+        assert_eq!(rules.detect(&state, &print(1, "alice2 was telefragged by alice\n")), Some(FragEvent{
+            killer: Some(0), victim: Some(1), weapon: "TELEFRAG".to_string(), kind: FragKind::Frag,
+        }));
+        assert_eq!(rules.detect(&state, &print(1, "alice2 becomes bored with life\n")).unwrap().kind, FragKind::Suicide);
+        assert_eq!(rules.detect(&state, &print(1, "alice cratered\n")).unwrap().victim, Some(0));
+        assert_eq!(rules.detect(&state, &print(3, "alice cratered\n")), None);
+        assert_eq!(rules.detect(&state, &print(1, "bob cratered\n")), None);
+
+        assert_eq!(FragRules::parse("#DEFINE OBITUARY PLAYER_DEATH AXE \" died\"").unwrap_err(),
+            FragFileError::UnknownWeaponClass(1, "AXE".to_string()));
+    }
+
+    #[test]
+    fn prefixed_names() {
+        let ascii_converter = AsciiConverter::new();
+        let mut state = State::new();
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 0, uid: 1, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice") }),
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 1, uid: 2, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice 2") }),
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 2, uid: 3, userinfo: ascii_converter.convert_to_stringbyte("\\name\\bob") }),
+        ], MvdTarget{ to: 0, command: DemoCommand::All }).unwrap();
+        let print = |text: &str| Print{ from: 1, message: ascii_converter.convert_to_stringbyte(text) };
+
+        let rules = FragRules::parse(concat!(
+            "#DEFINE WEAPON_CLASS AXE \"Axe\"\n",
+            "#DEFINE OBITUARY PLAYER_DEATH AXE \" 2 died\"\n",
+            "#DEFINE OBITUARY PLAYER_DEATH AXE \" died\"\n",
+            "#DEFINE OBITUARY X_FRAGS_Y AXE \" 2 axed \"\n",
+            "#DEFINE OBITUARY X_FRAGS_Y AXE \" axed \"\n",
+        )).unwrap();
+        assert_eq!(rules.detect(&state, &print("alice 2 died\n")).unwrap().victim, Some(1));
+        assert_eq!(rules.detect(&state, &print("alice died\n")).unwrap().victim, Some(0));
+        let event = rules.detect(&state, &print("alice 2 axed bob\n")).unwrap();
+        assert_eq!((event.killer, event.victim), (Some(1), Some(2)));
+        let event = rules.detect(&state, &print("bob axed alice 2\n")).unwrap();
+        assert_eq!((event.killer, event.victim), (Some(2), Some(1)));
+    }
+}
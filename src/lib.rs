@@ -12,10 +12,14 @@ pub mod utils;
 pub mod protocol;
 #[cfg(feature = "mvd")]
 pub mod mvd;
+#[cfg(feature = "qwd")]
+pub mod qwd;
 #[cfg(feature = "state")]
 pub mod state;
 #[cfg(feature = "network")]
 pub mod network;
+#[cfg(feature = "frags")]
+pub mod frags;
 
 #[cfg(feature = "crc")]
 pub mod crc;
@@ -39,7 +43,7 @@ mod tests {
     #[test]
     fn message_parsing() {
         let b: Vec<u8> = vec![8, 2, 0x68, 0x65, 0x6c, 0x6c, 0x6f,0x0];
-        let mut message = Message::new(Box::new(b.clone()), 0, b.len(), false, MessageFlags {..Default::default()}, None, MessageType::Connection);
+        let mut message = Message::new(b.as_slice(), 0, b.len(), false, MessageFlags {..Default::default()}, None, MessageType::Connection);
         message.trace.enabled = true;
         let msg_cmd = match message.read_u8(false) {
             Ok(cmd) => cmd,
@@ -62,4 +66,24 @@ mod tests {
             _ => { panic!("its not print!");},
         }
     }
+
+    #[test]
+    fn message_borrows() {
+        use std::borrow::Cow;
+        let b: Vec<u8> = vec![0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0, 1, 2, 3];
+        let mut message = Message::new(b.as_slice(), 0, b.len(), false, MessageFlags {..Default::default()}, None, MessageType::Connection);
+        let s = message.read_stringbyte_cow(false).unwrap();
+        assert!(matches!(s, Cow::Borrowed(b"hello")));
+        let bytes = message.read_bytes_cow(3, false).unwrap();
+        assert!(matches!(bytes, Cow::Borrowed(&[1, 2, 3])));
+
+        let b: Vec<u8> = vec![8, 2, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0];
+        let mut message = Message::new(b.as_slice(), 1, b.len() - 1, false, MessageFlags {..Default::default()}, None, MessageType::Connection);
+        match ServerClient::Print.read_message(&mut message) {
+            Ok(crate::protocol::types::ServerMessage::Print(p)) => {
+                assert!(matches!(p.message.bytes, Cow::Borrowed(b"hello")));
+            },
+            _ => { panic!("its not print!");},
+        }
+    }
 }
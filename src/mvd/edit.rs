@@ -0,0 +1,274 @@
+use crate::mvd::{Mvd, MvdFrame, MvdWriter};
+use crate::protocol::errors::MvdParseError;
+use crate::protocol::types::{ServerMessage, StringByte};
+use crate::state::{ServerInfo, State};
+
+// demo time is stored in ms, adding them up drifts a little
+const TIME_EPSILON: f64 = 0.0005;
+
+fn parse(input: &[u8]) -> Result<Mvd<'_>, MvdParseError> {
+    Mvd::new(input,
+#[cfg(feature = "ascii_strings")]
+        None,
+#[cfg(feature = "trace")]
+        false,
+        ).map_err(MvdParseError::Io)
+}
+
+/// a standalone demo of the frames between `start_time` and `end_time`
+///
+/// the gamestate at `start_time` is written first, so the demo can be played from there
+pub fn cut(input: &[u8], start_time: f64, end_time: f64) -> Result<Vec<u8>, MvdParseError> {
+    let mut state = State::new();
+    let mut applied = false;
+    let mut writer = MvdWriter::new();
+    let mut started = false;
+    for frame in parse(input)? {
+        let frame = frame?;
+        if frame.time > end_time + TIME_EPSILON {
+            break;
+        }
+        if frame.time < start_time - TIME_EPSILON {
+            state.apply_messages_mvd(&frame.messages, frame.last.clone())?;
+            applied = true;
+            continue;
+        }
+        if !started {
+            if applied {
+                writer.write_state(&state, 0.0)?;
+            }
+            started = true;
+        }
+        writer.write_frame(&MvdFrame {
+            time: frame.time - start_time,
+            ..frame
+        })?;
+        if writer.finished {
+            break;
+        }
+    }
+    // nothing is left after `start_time`, the demo still gets the gamestate
+    if !started && applied {
+        writer.write_state(&state, 0.0)?;
+    }
+    Ok(writer.finish()?)
+}
+
+/// one demo per [`ServerMessage::Serverdata`], each starting at time 0
+pub fn split_by_map(input: &[u8]) -> Result<Vec<Vec<u8>>, MvdParseError> {
+    let mut demos = vec![];
+    let mut writer = MvdWriter::new();
+    let mut map_start_time = 0.0;
+    let mut written = false;
+    for frame in parse(input)? {
+        let frame = frame?;
+        let is_serverdata = frame.messages.iter().any(|m| matches!(m, ServerMessage::Serverdata(_)));
+        if is_serverdata && written {
+            demos.push(std::mem::take(&mut writer).finish()?);
+            map_start_time = frame.time;
+        }
+        if writer.finished {
+            continue;
+        }
+        writer.write_frame(&MvdFrame {
+            time: frame.time - map_start_time,
+            ..frame
+        })?;
+        written = true;
+    }
+    if written {
+        demos.push(writer.finish()?);
+    }
+    Ok(demos)
+}
+
+fn is_gamestate(message: &ServerMessage) -> bool {
+    matches!(message,
+        ServerMessage::Serverdata(_) | ServerMessage::Soundlist(_) | ServerMessage::Modellist(_) |
+        ServerMessage::Spawnbaseline(_) | ServerMessage::Spawnstatic(_) | ServerMessage::Spawnstaticsound(_))
+}
+
+/// errors with the name of `field` if the values differ
+fn check_same(field: &'static str, first: &StringByte, other: &StringByte) -> Result<(), MvdParseError> {
+    if first.bytes != other.bytes {
+        return Err(MvdParseError::ConcatMismatch(field,
+                String::from_utf8_lossy(&first.bytes).to_string(),
+                String::from_utf8_lossy(&other.bytes).to_string()));
+    }
+    Ok(())
+}
+
+/// joins demos of the same map and server into one, demo time continues where the previous demo ended
+///
+/// the gamestate of the following demos and the EndOfDemo of all but the last are dropped
+pub fn concat(inputs: &[&[u8]]) -> Result<Vec<u8>, MvdParseError> {
+    let mut writer = MvdWriter::new();
+    let mut first_serverdata = None;
+    let mut first_hostname = None;
+    for (i, input) in inputs.iter().enumerate() {
+        let last_demo = i == inputs.len() - 1;
+        let time_offset = writer.time;
+        let mut serverinfo = ServerInfo::default();
+        for frame in parse(input)? {
+            let mut frame = frame?;
+            for message in &frame.messages {
+                match message {
+                    ServerMessage::Serverdata(serverdata) => match &first_serverdata {
+                        None => first_serverdata = Some(serverdata.clone()),
+                        Some(first) => {
+                            check_same("map", &first.map, &serverdata.map)?;
+                            check_same("gamedir", &first.gamedir, &serverdata.gamedir)?;
+                        },
+                    },
+                    ServerMessage::Stufftext(stufftext) => {
+                        serverinfo.update_from_stufftext(&stufftext.text.bytes);
+                    },
+                    ServerMessage::Serverinfo(data) => serverinfo.update_key_value(&data.key, &data.value),
+                    _ => {},
+                }
+            }
+            if i > 0 {
+                frame.messages.retain(|m| !is_gamestate(m));
+            }
+            if !last_demo {
+                frame.messages.retain(|m| !matches!(m, ServerMessage::Disconnect(_)));
+            }
+            frame.time += time_offset;
+            writer.write_frame(&frame)?;
+        }
+        match &first_hostname {
+            None => first_hostname = Some(serverinfo.hostname),
+            Some(first) => check_same("hostname", first, &serverinfo.hostname)?,
+        }
+    }
+    Ok(writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{scan_info, MvdFrame, MvdTarget, MvdWriter};
+    use crate::mvd::tests::{new_mvd, parse};
+    use crate::mvd::edit::{concat, cut, split_by_map};
+    use crate::protocol::errors::MvdParseError;
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    fn write_map(writer: &mut MvdWriter, map: &str, time: f64, frames: i16) {
+        let ascii_converter = AsciiConverter::new();
+        writer.write_messages(time, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+            ServerMessage::Serverdata(Serverdata{
+                protocol: ProtocolVersion::Standard,
+                servercount: 5,
+                gamedir: ascii_converter.convert_to_stringbyte("qw"),
+                map: ascii_converter.convert_to_stringbyte(map),
+                ..Default::default()
+            }),
+            ServerMessage::Modellist(Modellist{
+                start: 0,
+                models: vec![ascii_converter.convert_to_stringbyte(format!("maps/{}.bsp", map))],
+                offset: 0,
+            }),
+        ]).unwrap();
+        for i in 1..frames {
+            writer.write_messages(time + i as f64 * 0.1, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: i }),
+            ]).unwrap();
+        }
+    }
+
+    fn demo(map: &str, frames: i16) -> Vec<u8> {
+        let mut writer = MvdWriter::new();
+        write_map(&mut writer, map, 0.0, frames);
+        writer.finish().unwrap()
+    }
+
+    fn serverdata_count(frames: &[MvdFrame]) -> usize {
+        frames.iter()
+            .flat_map(|frame| &frame.messages)
+            .filter(|m| matches!(m, ServerMessage::Serverdata(_)))
+            .count()
+    }
+
+    #[test]
+    fn cut_demo() {
+        let buffer = cut(&demo("dm3", 100), 2.0, 3.0).unwrap();
+        let mvd = new_mvd(buffer);
+        let frames: Vec<_> = mvd.with_state().map(|r| r.unwrap()).collect();
+        assert!(matches!(frames[0].0.messages[0], ServerMessage::Serverdata(_)));
+        let (frame, state) = frames.last().unwrap();
+        assert!(frame.time <= 1.0 + 0.0001);
+        assert_eq!(state.players[&1].frags, 30);
+        assert_eq!(String::from_utf8_lossy(&state.models[0].bytes), "maps/dm3.bsp");
+    }
+
+    #[test]
+    fn cut_keeps_gamestate() {
+        let ascii_converter = AsciiConverter::new();
+        let mut writer = MvdWriter::new();
+        write_map(&mut writer, "dm3", 0.0, 10);
+        writer.write_messages(1.0, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+            ServerMessage::Stufftext(Stufftext{ text: ascii_converter.convert_to_stringbyte("fullserverinfo \"\\hostname\\qw.example.com\\status\\Standby\"\n") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("status"), value: ascii_converter.convert_to_stringbyte("Countdown") }),
+            ServerMessage::Lightstyle(Lightstyle{ index: 2, style: ascii_converter.convert_to_stringbyte("az") }),
+            ServerMessage::Cdtrack(Cdtrack{ track: 4 }),
+        ]).unwrap();
+        for i in 11..40 {
+            writer.write_messages(i as f64 * 0.1, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: i }),
+            ]).unwrap();
+        }
+        let input = writer.finish().unwrap();
+
+        let info = scan_info(&cut(&input, 2.0, 3.0).unwrap()).unwrap();
+        assert_eq!(info.hostname, "qw.example.com");
+        assert_eq!(info.serverinfo_value("status"), Some("Countdown"));
+
+        let buffer = cut(&input, 10.0, 11.0).unwrap();
+        let mvd = new_mvd(buffer);
+        let (_, state) = mvd.with_state().map(|r| r.unwrap()).last().unwrap();
+        assert_eq!(state.serverinfo.status.bytes.as_ref(), b"Countdown");
+        assert_eq!(state.lightstyles[&2].bytes.as_ref(), b"az");
+        assert_eq!(state.cdtrack, 4);
+        assert_eq!(state.players[&1].frags, 39);
+    }
+
+    #[test]
+    fn split_and_concat() {
+        let first = demo("dm3", 20);
+        let frames = parse(concat(&[&first, &demo("dm3", 30)]).unwrap());
+        assert_eq!(serverdata_count(&frames), 1);
+        assert!(frames.windows(2).all(|f| f[0].time <= f[1].time && f[0].frame + 1 == f[1].frame));
+        assert!((frames.last().unwrap().time - 4.8).abs() < 0.01);
+        assert!(matches!(concat(&[&first, &demo("e1m1", 10)]), Err(MvdParseError::ConcatMismatch("map", _, _))));
+
+        let on_server = |hostname: &str| {
+            let ascii_converter = AsciiConverter::new();
+            let mut writer = MvdWriter::new();
+            write_map(&mut writer, "dm3", 0.0, 10);
+            writer.write_messages(1.0, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("hostname"), value: ascii_converter.convert_to_stringbyte(hostname) }),
+            ]).unwrap();
+            writer.finish().unwrap()
+        };
+        assert!(concat(&[&on_server("qw.example.com"), &on_server("qw.example.com")]).is_ok());
+        match concat(&[&on_server("qw.example.com"), &on_server("qw.example.org")]) {
+            Err(MvdParseError::ConcatMismatch(field, first, other)) => {
+                assert_eq!(field, "hostname");
+                assert_eq!(first, "qw.example.com");
+                assert_eq!(other, "qw.example.org");
+            },
+            _ => panic!("hostnames differ"),
+        }
+
+        let mut writer = MvdWriter::new();
+        write_map(&mut writer, "dm3", 0.0, 20);
+        write_map(&mut writer, "dm4", 5.0, 30);
+        let maps = split_by_map(&writer.finish().unwrap()).unwrap();
+        assert_eq!(maps.len(), 2);
+        for map in maps {
+            let frames = parse(map);
+            assert_eq!(serverdata_count(&frames), 1);
+            assert_eq!(frames[0].time, 0.0);
+        }
+    }
+}
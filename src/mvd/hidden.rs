@@ -0,0 +1,351 @@
+use serde::Serialize;
+use crate::protocol::message::{Message, MessageType};
+use crate::protocol::message::errors::MessageError;
+use crate::protocol::types::AngleVector;
+
+/// mvdsv stores these in [`crate::protocol::types::DemoCommand::Multiple`] blocks sent to no one,
+/// each one is prefixed with its length (u32) and type (u16)
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
+pub enum MvdHiddenMessage {
+    AntilagPosition(MvdHiddenAntilagPosition),
+    Usercmd(MvdHiddenUsercmd),
+    UsercmdWeapons(MvdHiddenUsercmdWeapons),
+    /// a part of the demoinfo json, the parts need to be joined in block order
+    Demoinfo(MvdHiddenDemoinfo),
+    DamageDone(MvdHiddenDamageDone),
+    /// ms that passed while the game was paused
+    PausedDuration(u8),
+    ExtensionInfo(MvdHiddenExtensionInfo),
+    Unknown(MvdHiddenUnknown),
+}
+
+const HIDDEN_ANTILAG_POSITION: u16 = 0x0000;
+const HIDDEN_USERCMD: u16 = 0x0001;
+const HIDDEN_USERCMD_WEAPONS: u16 = 0x0002;
+const HIDDEN_DEMOINFO: u16 = 0x0003;
+const HIDDEN_DAMAGE_DONE: u16 = 0x0007;
+const HIDDEN_PAUSED_DURATION: u16 = 0x000A;
+const HIDDEN_EXTENSION_INFO: u16 = 0xFFFF;
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenAntilagPlayer {
+    pub player_number: u8,
+    pub flags: u8,
+    pub origin: [f32; 3],
+    pub msec: f32,
+}
+
+/// where the server rewound the other players to for `player_number`
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenAntilagPosition {
+    pub player_number: u8,
+    pub incoming_sequence: i32,
+    pub server_frame: i32,
+    pub target_time: f32,
+    pub players: Vec<MvdHiddenAntilagPlayer>,
+}
+
+/// the usercmd as the server received it from `player_number`
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenUsercmd {
+    pub player_number: u8,
+    pub dropped: u8,
+    pub msec: u8,
+    pub angle: AngleVector,
+    pub forward: i16,
+    pub side: i16,
+    pub up: i16,
+    pub buttons: u8,
+    pub impulse: u8,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenUsercmdWeapons {
+    pub player_number: u8,
+    pub items: i32,
+    pub ammo: [u8; 4],
+    pub result: u8,
+    pub weapon_priority: Vec<u8>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenDemoinfo {
+    pub block: u16,
+    pub content: Vec<u8>,
+}
+
+/// `flags` has the deathtype in the lower bits, 0x80 is set for splash damage
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenDamageDone {
+    pub flags: u8,
+    pub attacker: u16,
+    pub target: u16,
+    pub damage: u16,
+}
+
+/// MVD_PEXT1 extension info, the block type is 0xffff and the actual type follows as u16
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenExtensionInfo {
+    pub r#type: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd, Default)]
+pub struct MvdHiddenUnknown {
+    pub r#type: u16,
+    pub data: Vec<u8>,
+}
+
+impl MvdHiddenMessage {
+    /// reads all hidden messages of a `size` long block, unknown or broken ones are kept as
+    /// [`MvdHiddenMessage::Unknown`]
+    pub fn read_block(message: &mut Message, size: usize) -> Result<Vec<MvdHiddenMessage>, MessageError> {
+        let end = message.position + size;
+        let mut hidden = vec![];
+        while message.position + 6 <= end {
+            let length = message.read_u32(false)? as usize;
+            let r#type = message.read_u16(false)?;
+            let length = length.min(end - message.position);
+            let data = message.read_bytes_cow(length as u32, false)?;
+            hidden.push(Self::read_data(r#type, &data, message));
+        }
+        message.position = end;
+        Ok(hidden)
+    }
+
+    fn read_data(r#type: u16, data: &[u8], parent: &Message) -> MvdHiddenMessage {
+        let mut message = Message::new(data, 0, data.len(), false, parent.flags,
+#[cfg(feature = "ascii_strings")]
+            Some(parent.ascii_converter.clone()),
+            MessageType::Mvd);
+        let parsed = match r#type {
+            HIDDEN_ANTILAG_POSITION => Self::read_antilag_position(&mut message),
+            HIDDEN_USERCMD => Self::read_usercmd(&mut message),
+            HIDDEN_USERCMD_WEAPONS => Self::read_usercmd_weapons(&mut message),
+            HIDDEN_DEMOINFO => Self::read_demoinfo(&mut message),
+            HIDDEN_DAMAGE_DONE => Self::read_damage_done(&mut message),
+            HIDDEN_PAUSED_DURATION => message.read_u8(false).map(MvdHiddenMessage::PausedDuration),
+            HIDDEN_EXTENSION_INFO => Self::read_extension_info(&mut message),
+            _ => Err(MessageError::StringError(format!("unknown hidden message type {}", r#type))),
+        };
+        match parsed {
+            Ok(hidden) => hidden,
+            Err(_) => MvdHiddenMessage::Unknown(MvdHiddenUnknown{
+                r#type,
+                data: data.to_vec(),
+            }),
+        }
+    }
+
+    fn read_antilag_position(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        let player_number = message.read_u8(false)?;
+        let count = message.read_u8(false)?;
+        let incoming_sequence = message.read_i32(false)?;
+        let server_frame = message.read_i32(false)?;
+        let target_time = message.read_f32(false)?;
+        let mut players = vec![];
+        for _ in 0..count {
+            players.push(MvdHiddenAntilagPlayer{
+                player_number: message.read_u8(false)?,
+                flags: message.read_u8(false)?,
+                origin: [message.read_f32(false)?, message.read_f32(false)?, message.read_f32(false)?],
+                msec: message.read_f32(false)?,
+            });
+        }
+        Ok(MvdHiddenMessage::AntilagPosition(MvdHiddenAntilagPosition{
+            player_number,
+            incoming_sequence,
+            server_frame,
+            target_time,
+            players,
+        }))
+    }
+
+    fn read_usercmd(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        Ok(MvdHiddenMessage::Usercmd(MvdHiddenUsercmd{
+            player_number: message.read_u8(false)?,
+            dropped: message.read_u8(false)?,
+            msec: message.read_u8(false)?,
+            angle: AngleVector{
+                x: message.read_f32(false)?,
+                y: message.read_f32(false)?,
+                z: message.read_f32(false)?,
+            },
+            forward: message.read_i16(false)?,
+            side: message.read_i16(false)?,
+            up: message.read_i16(false)?,
+            buttons: message.read_u8(false)?,
+            impulse: message.read_u8(false)?,
+        }))
+    }
+
+    fn read_usercmd_weapons(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        let player_number = message.read_u8(false)?;
+        let items = message.read_i32(false)?;
+        let ammo = [message.read_u8(false)?, message.read_u8(false)?, message.read_u8(false)?, message.read_u8(false)?];
+        let result = message.read_u8(false)?;
+        let weapon_priority = message.read_stringbyte_cow(false)?.into_owned();
+        Ok(MvdHiddenMessage::UsercmdWeapons(MvdHiddenUsercmdWeapons{
+            player_number,
+            items,
+            ammo,
+            result,
+            weapon_priority,
+        }))
+    }
+
+    fn read_demoinfo(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        let block = message.read_u16(false)?;
+        let content = message.read_bytes((message.length - message.position) as u32, false)?;
+        Ok(MvdHiddenMessage::Demoinfo(MvdHiddenDemoinfo{
+            block,
+            content,
+        }))
+    }
+
+    fn read_damage_done(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        Ok(MvdHiddenMessage::DamageDone(MvdHiddenDamageDone{
+            flags: message.read_u8(false)?,
+            attacker: message.read_u16(false)?,
+            target: message.read_u16(false)?,
+            damage: message.read_u16(false)?,
+        }))
+    }
+
+    fn read_extension_info(message: &mut Message) -> Result<MvdHiddenMessage, MessageError> {
+        let r#type = message.read_u16(false)?;
+        let data = message.read_bytes((message.length - message.position) as u32, false)?;
+        Ok(MvdHiddenMessage::ExtensionInfo(MvdHiddenExtensionInfo{
+            r#type,
+            data,
+        }))
+    }
+
+    /// writes the message with its length and type
+    pub fn write(&self, message: &mut Message) -> usize {
+        let mut data = Message::empty();
+        let r#type = match self {
+            MvdHiddenMessage::AntilagPosition(antilag) => {
+                data.write_u8(antilag.player_number);
+                data.write_u8(antilag.players.len() as u8);
+                data.write_i32(antilag.incoming_sequence);
+                data.write_i32(antilag.server_frame);
+                data.write_f32(antilag.target_time);
+                for player in &antilag.players {
+                    data.write_u8(player.player_number);
+                    data.write_u8(player.flags);
+                    for v in player.origin {
+                        data.write_f32(v);
+                    }
+                    data.write_f32(player.msec);
+                }
+                HIDDEN_ANTILAG_POSITION
+            },
+            MvdHiddenMessage::Usercmd(usercmd) => {
+                data.write_u8(usercmd.player_number);
+                data.write_u8(usercmd.dropped);
+                data.write_u8(usercmd.msec);
+                data.write_f32(usercmd.angle.x);
+                data.write_f32(usercmd.angle.y);
+                data.write_f32(usercmd.angle.z);
+                data.write_i16(usercmd.forward);
+                data.write_i16(usercmd.side);
+                data.write_i16(usercmd.up);
+                data.write_u8(usercmd.buttons);
+                data.write_u8(usercmd.impulse);
+                HIDDEN_USERCMD
+            },
+            MvdHiddenMessage::UsercmdWeapons(weapons) => {
+                data.write_u8(weapons.player_number);
+                data.write_i32(weapons.items);
+                for ammo in weapons.ammo {
+                    data.write_u8(ammo);
+                }
+                data.write_u8(weapons.result);
+                data.buffer.to_mut().extend_from_slice(&weapons.weapon_priority);
+                data.write_u8(0);
+                HIDDEN_USERCMD_WEAPONS
+            },
+            MvdHiddenMessage::Demoinfo(demoinfo) => {
+                data.write_u16(demoinfo.block);
+                data.buffer.to_mut().extend_from_slice(&demoinfo.content);
+                HIDDEN_DEMOINFO
+            },
+            MvdHiddenMessage::DamageDone(damage) => {
+                data.write_u8(damage.flags);
+                data.write_u16(damage.attacker);
+                data.write_u16(damage.target);
+                data.write_u16(damage.damage);
+                HIDDEN_DAMAGE_DONE
+            },
+            MvdHiddenMessage::PausedDuration(msec) => {
+                data.write_u8(*msec);
+                HIDDEN_PAUSED_DURATION
+            },
+            MvdHiddenMessage::ExtensionInfo(extension_info) => {
+                data.write_u16(extension_info.r#type);
+                data.buffer.to_mut().extend_from_slice(&extension_info.data);
+                HIDDEN_EXTENSION_INFO
+            },
+            MvdHiddenMessage::Unknown(unknown) => {
+                data.buffer.to_mut().extend_from_slice(&unknown.data);
+                unknown.r#type
+            },
+        };
+        let mut size = message.write_u32(data.buffer.len() as u32);
+        size += message.write_u16(r#type);
+        message.buffer.to_mut().extend_from_slice(&data.buffer);
+        message.position += data.buffer.len();
+        size + data.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{MvdFrame, MvdTarget, MvdWriter};
+    use crate::mvd::tests::new_mvd;
+    use crate::mvd::hidden::*;
+    use crate::protocol::types::*;
+
+    #[test]
+    fn hidden_messages() {
+        let hidden = vec![
+            MvdHiddenMessage::Usercmd(MvdHiddenUsercmd{
+                player_number: 3,
+                msec: 13,
+                angle: AngleVector{ x: 1.5, y: 90.0, z: 0.0 },
+                forward: 800,
+                side: -350,
+                buttons: 1,
+                ..Default::default()
+            }),
+            MvdHiddenMessage::DamageDone(MvdHiddenDamageDone{ flags: 0x80 | 6, attacker: 3, target: 5, damage: 110 }),
+            MvdHiddenMessage::Demoinfo(MvdHiddenDemoinfo{ block: 0, content: b"{\"players\":[]}".to_vec() }),
+            MvdHiddenMessage::ExtensionInfo(MvdHiddenExtensionInfo{ r#type: 1, data: vec![1, 0, 0, 0] }),
+            MvdHiddenMessage::Unknown(MvdHiddenUnknown{ r#type: 0x1234, data: vec![1, 2, 3] }),
+        ];
+        let mut writer = MvdWriter::new();
+        writer.write_frame(&MvdFrame{
+            messages: vec![],
+            hidden: hidden.clone(),
+            frame: 0,
+            time: 0.1,
+            last: MvdTarget{ to: 0, command: DemoCommand::Multiple },
+        }).unwrap();
+        let buffer = writer.finish().unwrap();
+
+        let mut mvd = new_mvd(buffer);
+        let frame = mvd.next().unwrap().unwrap();
+        assert_eq!(frame.hidden, hidden);
+        assert!(frame.messages.is_empty());
+
+        // a 0xffff block without the actual type
+        let mut message = Message::empty();
+        MvdHiddenMessage::Unknown(MvdHiddenUnknown{ r#type: 0xFFFF, data: vec![1] }).write(&mut message);
+        let size = message.buffer.len();
+        message.position = 0;
+        message.length = size;
+        assert_eq!(MvdHiddenMessage::read_block(&mut message, size).unwrap(),
+            vec![MvdHiddenMessage::Unknown(MvdHiddenUnknown{ r#type: 0xFFFF, data: vec![1] })]);
+    }
+}
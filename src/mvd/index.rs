@@ -0,0 +1,187 @@
+use serde::Serialize;
+use crate::mvd::{Mvd, MvdTarget};
+use crate::protocol::message::MessageFlags;
+use crate::protocol::errors::MvdParseError;
+use crate::state::State;
+
+/// where a frame starts in the demo
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct MvdIndexEntry {
+    pub frame: u32,
+    pub time: f64,
+    pub offset: usize,
+}
+
+/// the state after all frames before `frame` were applied and what is needed to continue parsing from there
+#[derive(Serialize, Clone, Debug)]
+pub struct MvdKeyframe {
+    pub frame: u32,
+    pub time: f64,
+    pub offset: usize,
+    pub last: MvdTarget,
+    pub flags: MessageFlags,
+    pub state: State,
+}
+
+/// frame offsets and periodic [`State`] snapshots of a demo, see [`Mvd::build_index`]
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MvdIndex {
+    /// seconds of demo time between two keyframes
+    pub interval: f64,
+    pub frames: Vec<MvdIndexEntry>,
+    pub keyframes: Vec<MvdKeyframe>,
+}
+
+impl MvdIndex {
+    /// the last frame with a time lower or equal to `time`
+    pub fn frame_at_time(&self, time: f64) -> Option<u32> {
+        let index = self.frames.partition_point(|entry| entry.time <= time);
+        if index == 0 {
+            return None;
+        }
+        Some(self.frames[index - 1].frame)
+    }
+
+    /// the last keyframe from which `frame` can be reached
+    fn keyframe_before(&self, frame: u32) -> Option<&MvdKeyframe> {
+        let index = self.keyframes.partition_point(|keyframe| keyframe.frame <= frame);
+        if index == 0 {
+            return None;
+        }
+        Some(&self.keyframes[index - 1])
+    }
+}
+
+impl Mvd<'_> {
+    fn keyframe(&self, state: &State) -> MvdKeyframe {
+        MvdKeyframe {
+            frame: self.frame,
+            time: self.time,
+            offset: self.message.position,
+            last: self.last.clone(),
+            flags: self.message.flags,
+            state: state.clone(),
+        }
+    }
+
+    fn restore_keyframe(&mut self, keyframe: &MvdKeyframe) {
+        self.frame = keyframe.frame;
+        self.time = keyframe.time;
+        self.message.position = keyframe.offset;
+        self.last = keyframe.last.clone();
+        self.message.flags = keyframe.flags;
+        self.finished = false;
+        self.failed = false;
+    }
+
+    /// parses the whole demo once, a [`State`] snapshot is kept every `interval` seconds
+    ///
+    /// parsing starts again from the beginning afterwards
+    pub fn build_index(&mut self, interval: f64) -> Result<MvdIndex, MvdParseError> {
+        if self.reader.is_some() {
+            return Err(MvdParseError::NotSeekable);
+        }
+        let mut state = State::new();
+        let start = MvdKeyframe {
+            frame: 0,
+            time: 0.0,
+            offset: 0,
+            last: MvdTarget::default(),
+            flags: MessageFlags::new_empty(),
+            state: state.clone(),
+        };
+        self.restore_keyframe(&start);
+        let mut index = MvdIndex {
+            interval,
+            frames: vec![],
+            keyframes: vec![start.clone()],
+        };
+
+        while !self.finished && self.message.position < self.message.length {
+            let offset = self.message.position;
+            let frame = self.parse_frame()?;
+            index.frames.push(MvdIndexEntry {
+                frame: frame.frame,
+                time: frame.time,
+                offset,
+            });
+            state.apply_messages_mvd(&frame.messages, frame.last)?;
+            let last_keyframe_time = index.keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+            if self.time - last_keyframe_time >= interval {
+                index.keyframes.push(self.keyframe(&state));
+            }
+        }
+
+        self.restore_keyframe(&start);
+        Ok(index)
+    }
+
+    /// continues parsing after `frame`, returns the [`State`] with every frame up to `frame` applied
+    pub fn seek_to_frame(&mut self, index: &MvdIndex, frame: u32) -> Result<State, MvdParseError> {
+        if self.reader.is_some() {
+            return Err(MvdParseError::NotSeekable);
+        }
+        let keyframe = match index.keyframe_before(frame) {
+            Some(keyframe) => keyframe,
+            None => return Err(MvdParseError::NoKeyframe(frame)),
+        };
+        self.restore_keyframe(keyframe);
+        let mut state = keyframe.state.clone();
+        while self.frame <= frame && !self.finished && self.message.position < self.message.length {
+            let frame = self.parse_frame()?;
+            state.apply_messages_mvd(&frame.messages, frame.last)?;
+        }
+        Ok(state)
+    }
+
+    /// same as [`Mvd::seek_to_frame`] with the last frame at or before `time`
+    pub fn seek_to_time(&mut self, index: &MvdIndex, time: f64) -> Result<State, MvdParseError> {
+        match index.frame_at_time(time) {
+            Some(frame) => self.seek_to_frame(index, frame),
+            None => {
+                let keyframe = match index.keyframes.first() {
+                    Some(keyframe) => keyframe,
+                    None => return Err(MvdParseError::NoKeyframe(0)),
+                };
+                self.restore_keyframe(keyframe);
+                Ok(keyframe.state.clone())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{MvdIndex, MvdTarget, MvdWriter};
+    use crate::mvd::tests::new_mvd;
+    use crate::protocol::errors::MvdParseError;
+    use crate::protocol::types::*;
+
+    #[test]
+    fn seek() {
+        let mut writer = MvdWriter::new();
+        for i in 0..100 {
+            writer.write_messages(i as f64 * 0.1, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: i }),
+            ]).unwrap();
+        }
+        let buffer = writer.finish().unwrap();
+        let mut mvd = new_mvd(buffer);
+        let index = mvd.build_index(2.0).unwrap();
+        assert_eq!(index.frames.len(), 101);
+        assert_eq!(index.keyframes.len(), 5);
+
+        let state = mvd.seek_to_time(&index, 5.25).unwrap();
+        assert_eq!(state.players[&1].frags, 52);
+        let frame = mvd.next().unwrap().unwrap();
+        assert_eq!(frame.frame, 53);
+
+        let empty = MvdIndex::default();
+        assert!(matches!(mvd.seek_to_frame(&empty, 10), Err(MvdParseError::NoKeyframe(10))));
+        assert!(matches!(mvd.seek_to_time(&empty, 1.0), Err(MvdParseError::NoKeyframe(0))));
+
+        let state = mvd.seek_to_frame(&index, 10).unwrap();
+        assert_eq!(state.players[&1].frags, 10);
+        assert_eq!(mvd.count(), 90);
+    }
+}
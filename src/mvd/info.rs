@@ -0,0 +1,308 @@
+use serde::Serialize;
+use crate::mvd::Mvd;
+use crate::protocol::errors::MvdParseError;
+use crate::protocol::message::Message;
+use crate::protocol::types::*;
+use crate::utils::ascii_converter::AsciiConverter;
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct DemoInfoPlayer {
+    pub player_number: u8,
+    pub name: String,
+    pub team: String,
+    pub spectator: bool,
+    pub frags: i16,
+}
+
+/// what [`scan_info`] found out about a demo
+#[derive(Serialize, Clone, PartialEq, Debug, Default)]
+pub struct DemoInfo {
+    pub map: String,
+    pub level_name: String,
+    pub gamedir: String,
+    pub hostname: String,
+    pub serverinfo: Vec<(String, String)>,
+    pub players: Vec<DemoInfoPlayer>,
+    pub teams: Vec<String>,
+    /// demo time when intermission was reached or the demo ended
+    pub duration: f64,
+    /// taken from the ktx countdown, guessed from teamplay and the players otherwise
+    pub mode: String,
+    pub intermission: bool,
+}
+
+impl DemoInfo {
+    pub fn serverinfo_value(&self, key: &str) -> Option<&str> {
+        self.serverinfo.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn set_serverinfo(&mut self, key: String, value: String) {
+        match self.serverinfo.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.serverinfo.push((key, value)),
+        }
+    }
+
+    fn player(&mut self, player_number: u8) -> &mut DemoInfoPlayer {
+        let index = match self.players.iter().position(|p| p.player_number == player_number) {
+            Some(index) => index,
+            None => {
+                self.players.push(DemoInfoPlayer{ player_number, ..Default::default() });
+                self.players.sort_by_key(|p| p.player_number);
+                self.players.iter().position(|p| p.player_number == player_number).unwrap()
+            },
+        };
+        &mut self.players[index]
+    }
+
+    fn guess_mode(&self) -> String {
+        let players: Vec<&DemoInfoPlayer> = self.players.iter().filter(|p| !p.spectator).collect();
+        let teamplay = self.serverinfo_value("teamplay").unwrap_or("0");
+        if teamplay == "0" || teamplay.is_empty() {
+            return if players.len() == 2 { "duel".to_string() } else { "ffa".to_string() };
+        }
+        let sizes: Vec<usize> = self.teams.iter()
+            .map(|team| players.iter().filter(|p| p.team == *team).count())
+            .collect();
+        match sizes.as_slice() {
+            [a, b] if a == b => format!("{}on{}", a, b),
+            _ => "team".to_string(),
+        }
+    }
+}
+
+/// `\key\value` pairs of a userinfo or serverinfo string
+fn info_values(ascii_converter: &AsciiConverter, bytes: &[u8]) -> Vec<(String, String)> {
+    let bytes = bytes.strip_prefix(b"\\").unwrap_or(bytes);
+    let parts: Vec<String> = bytes.split(|b| *b == b'\\')
+        .map(|part| ascii_converter.convert(part))
+        .collect();
+    parts.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect()
+}
+
+/// reads the metadata of a demo without keeping track of the state
+///
+/// blocks that are sent to no one or only contain stats are skipped. Entity and player
+/// messages carry no size, so they are still decoded and then dropped. Scanning stops at intermission
+pub fn scan_info(input: &[u8]) -> Result<DemoInfo, MvdParseError> {
+    let mut mvd = Mvd::new(input,
+#[cfg(feature = "ascii_strings")]
+        None,
+#[cfg(feature = "trace")]
+        false,
+        ).map_err(MvdParseError::Io)?;
+    let message = &mut mvd.message;
+    let ascii_converter = AsciiConverter::new();
+    let mut info = DemoInfo::default();
+    let mut mode = None;
+    let mut time = 0.0;
+
+    'frames: while message.position < message.length {
+        time += message.read_u8(false)? as f64 * 0.001;
+        let cmd = message.read_u8(false)?;
+        let skip = match DemoCommand::try_from(cmd & 7) {
+            Ok(DemoCommand::Set) => {
+                message.read_u32(false)?;
+                message.read_u32(false)?;
+                continue;
+            },
+            Ok(DemoCommand::Multiple) => message.read_u32(false)? == 0,
+            Ok(DemoCommand::Stats) => true,
+            Ok(DemoCommand::Command) | Err(_) => return Err(MvdParseError::UnhandledCommand(cmd & 7)),
+            Ok(_) => false,
+        };
+        let size = message.read_u32(false)? as usize;
+        let end = message.position + size;
+        if end > message.length {
+            return Err(MvdParseError::ReadBeyondSize(message.length, message.position, size));
+        }
+        if !skip && scan_block(message, end, &ascii_converter, &mut info, &mut mode)? {
+            break 'frames;
+        }
+        message.position = end;
+    }
+
+    info.duration = time;
+    info.hostname = info.serverinfo_value("hostname").unwrap_or_default().to_string();
+    if let Some(map) = info.serverinfo_value("map") {
+        info.map = map.to_string();
+    }
+    info.players.retain(|p| !p.name.is_empty());
+    let mut teams: Vec<String> = info.players.iter()
+        .filter(|p| !p.spectator && !p.team.is_empty())
+        .map(|p| p.team.clone())
+        .collect();
+    teams.sort();
+    teams.dedup();
+    info.teams = teams;
+    info.mode = match mode {
+        Some(mode) => mode,
+        None => info.guess_mode(),
+    };
+    Ok(info)
+}
+
+/// the mode of a ktx countdown centerprint, it starts with "Countdown: 5" and
+/// has a line like "Mode          Duel"
+fn countdown_mode(text: &str) -> Option<String> {
+    let mut lines = text.lines().map(|line| line.trim()).filter(|line| !line.is_empty());
+    if !lines.next()?.starts_with("Countdown:") {
+        return None;
+    }
+    lines.find_map(|line| {
+        let value = line.strip_prefix("Mode")?;
+        if !value.starts_with(char::is_whitespace) {
+            return None;
+        }
+        Some(value.trim().to_lowercase())
+    })
+}
+
+/// returns true once the demo ended or intermission was reached
+fn scan_block(message: &mut Message, end: usize, ascii_converter: &AsciiConverter, info: &mut DemoInfo, mode: &mut Option<String>) -> Result<bool, MvdParseError> {
+    while message.position < end {
+        let cmd = message.read_u8(false)?;
+        let cmd = match ServerClient::try_from(cmd) {
+            Ok(cmd) => cmd,
+            Err(_) => return Err(MvdParseError::UnhandledCommand(cmd)),
+        };
+        match cmd.read_message(message)? {
+            ServerMessage::Serverdata(serverdata) => {
+                message.flags.fte_protocol_extensions = serverdata.fte_protocol_extension;
+                message.flags.fte_protocol_extensions_2 = serverdata.fte_protocol_extension_2;
+                message.flags.mvd_protocol_extension = serverdata.mvd_protocol_extension;
+                info.gamedir = ascii_converter.convert(serverdata.gamedir.bytes.clone());
+                info.level_name = ascii_converter.convert(serverdata.map.bytes.clone());
+            },
+            ServerMessage::Stufftext(stufftext) => {
+                if let Some(serverinfo) = stufftext.text.bytes.strip_prefix(b"fullserverinfo ") {
+                    let serverinfo: Vec<u8> = serverinfo.iter().copied().filter(|b| *b != b'"' && *b != b'\n').collect();
+                    for (key, value) in info_values(ascii_converter, &serverinfo) {
+                        info.set_serverinfo(key, value);
+                    }
+                }
+            },
+            ServerMessage::Serverinfo(serverinfo) => {
+                info.set_serverinfo(ascii_converter.convert(serverinfo.key.bytes), ascii_converter.convert(serverinfo.value.bytes));
+            },
+            ServerMessage::Updateuserinfo(userinfo) => {
+                let values = info_values(ascii_converter, &userinfo.userinfo.bytes);
+                let value = |key: &str| values.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).unwrap_or_default();
+                let player = info.player(userinfo.player_number);
+                player.name = value("name");
+                player.team = value("team");
+                player.spectator = !value("*spectator").is_empty();
+            },
+            ServerMessage::Setinfo(setinfo) => {
+                let key = ascii_converter.convert(setinfo.key.bytes);
+                let value = ascii_converter.convert(setinfo.value.bytes);
+                let player = info.player(setinfo.player_number);
+                match key.as_str() {
+                    "name" => player.name = value,
+                    "team" => player.team = value,
+                    "*spectator" => player.spectator = !value.is_empty(),
+                    _ => {},
+                }
+            },
+            ServerMessage::Updatefrags(frags) => {
+                info.player(frags.player_number).frags = frags.frags;
+            },
+            ServerMessage::Centerprint(centerprint) => {
+                let text = ascii_converter.convert(centerprint.message.bytes);
+                if let Some(countdown_mode) = countdown_mode(&text) {
+                    *mode = Some(countdown_mode);
+                }
+            },
+            ServerMessage::Intermission(_) => {
+                info.intermission = true;
+                return Ok(true);
+            },
+            ServerMessage::Disconnect(_) => return Ok(true),
+            _ => {},
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{MvdTarget, MvdWriter};
+    use crate::mvd::info::{countdown_mode, scan_info};
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    #[test]
+    fn scan() {
+        let ascii_converter = AsciiConverter::new();
+        let all = MvdTarget{ to: 0, command: DemoCommand::All };
+        let mut writer = MvdWriter::new();
+        writer.write_messages(0.0, all.clone(), vec![
+            ServerMessage::Serverdata(Serverdata{
+                protocol: ProtocolVersion::Standard,
+                gamedir: ascii_converter.convert_to_stringbyte("qw"),
+                map: ascii_converter.convert_to_stringbyte("The Abandoned Base"),
+                ..Default::default()
+            }),
+            ServerMessage::Stufftext(Stufftext{ text: ascii_converter.convert_to_stringbyte("fullserverinfo \"\\hostname\\qw.example.com\\map\\dm3\\teamplay\\2\"\n") }),
+        ]).unwrap();
+        for (i, (name, team)) in [("alice", "red"), ("bob", "blue"), ("carol", "red"), ("dave", "blue")].iter().enumerate() {
+            writer.write_messages(0.0, all.clone(), vec![
+                ServerMessage::Updateuserinfo(Updateuserinfo{
+                    player_number: i as u8,
+                    uid: i as u32,
+                    userinfo: ascii_converter.convert_to_stringbyte(format!("\\name\\{}\\team\\{}", name, team)),
+                }),
+            ]).unwrap();
+        }
+        writer.write_messages(1.0, all.clone(), vec![
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 0, frags: 12 }),
+            ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+                player_number: 0,
+                flags: DfTypes::ORIGIN | DfTypes::WEAPONFRAME,
+                frame: 0,
+                origin: Some(CoordinateVectorOption{ x: Some(64.0), y: None, z: None }),
+                angle: None,
+                model: None,
+                skinnum: None,
+                effects: None,
+                weaponframe: Some(2),
+            })),
+            ServerMessage::Packetentities(Packetentities{ entities: vec![] }),
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 0, frags: 99 }),
+        ]).unwrap();
+        writer.write_messages(600.0, all.clone(), vec![
+            ServerMessage::Intermission(Intermission{ origin: CoordinateVector::default(), angle: AngleVector::default() }),
+        ]).unwrap();
+        writer.write_messages(601.0, all, vec![
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: 7 }),
+        ]).unwrap();
+
+        let info = scan_info(&writer.finish().unwrap()).unwrap();
+        assert_eq!(info.map, "dm3");
+        assert_eq!(info.level_name, "The Abandoned Base");
+        assert_eq!(info.hostname, "qw.example.com");
+        assert_eq!(info.teams, vec!["blue", "red"]);
+        assert_eq!(info.mode, "2on2");
+        assert_eq!(info.players[0].name, "alice");
+        // player and entity updates are read past
+        assert_eq!(info.players[0].frags, 99);
+        // nothing after intermission is read
+        assert_eq!(info.players[1].frags, 0);
+        assert!(info.intermission);
+        assert!((info.duration - 600.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn countdown() {
+        assert_eq!(countdown_mode("Countdown: 5\n\n\nDeathmatch  1\nMode          Duel\nSpawnmodel  KTX\n").as_deref(), Some("duel"));
+        assert_eq!(countdown_mode("\nCountdown: 1\nMode  Team\n").as_deref(), Some("team"));
+        assert_eq!(countdown_mode("Mode          Duel\n"), None);
+        assert_eq!(countdown_mode("Countdown: 5\nModel  player.mdl\n"), None);
+        assert_eq!(countdown_mode("welcome\nCountdown: 5\nMode  Duel\n"), None);
+    }
+}
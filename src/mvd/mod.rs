@@ -1,15 +1,36 @@
+use std::borrow::Cow;
+use std::io::Read;
+#[cfg(feature = "state")]
+use std::rc::Rc;
 use serde::Serialize;
 use crate::protocol::message::Message;
 use crate::protocol::message::MessageFlags;
 use crate::protocol::message::MessageType;
 use crate::protocol::types::*;
 use crate::protocol::errors::MvdParseError;
+#[cfg(feature = "state")]
+use crate::state::State;
 
 #[cfg(feature = "ascii_strings")]
 use crate::utils::ascii_converter::AsciiConverter;
 
 use crate::protocol::message::trace::*;
 
+pub mod writer;
+pub use writer::MvdWriter;
+pub mod hidden;
+pub use hidden::MvdHiddenMessage;
+pub mod info;
+pub use info::{scan_info, DemoInfo};
+#[cfg(feature = "state")]
+pub mod index;
+#[cfg(feature = "state")]
+pub use index::MvdIndex;
+#[cfg(feature = "state")]
+pub mod edit;
+#[cfg(feature = "state")]
+pub use edit::{concat, cut, split_by_map};
+
 
 #[derive(Serialize, Clone, PartialEq, Eq, Debug, PartialOrd)]
 pub struct MvdTarget {
@@ -26,30 +47,49 @@ impl Default for MvdTarget {
     }
 }
 
+/// how much is read from the reader at once when streaming
+pub const STREAM_READ_SIZE: usize = 64 * 1024;
+
+/// the source of a streaming [`Mvd`]
+pub struct MvdReader<'a>(Box<dyn Read + 'a>);
+
+impl std::fmt::Debug for MvdReader<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MvdReader")
+    }
+}
+
 #[derive(Serialize, Debug)]
-pub struct Mvd {
+pub struct Mvd<'a> {
     pub size: usize,
     pub finished: bool,
-    pub message: Message,
+    pub message: Message<'a>,
     pub last: MvdTarget,
     pub frame: u32,
     pub time: f64,
 #[cfg(feature = "trace")]
     pub trace: bool,
+    #[serde(skip)]
+    reader: Option<MvdReader<'a>>,
+    #[serde(skip)]
+    failed: bool,
 }
 
 #[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
-pub struct MvdFrame {
-    pub messages: Vec<ServerMessage>,
+pub struct MvdFrame<'a> {
+    pub messages: Vec<ServerMessage<'a>>,
+    /// mvdsv hidden data, see [`MvdHiddenMessage`]
+    pub hidden: Vec<MvdHiddenMessage>,
     pub frame: u32,
     pub time: f64,
     pub last: MvdTarget,
 }
 
-impl MvdFrame {
-    fn empty() -> MvdFrame {
+impl MvdFrame<'_> {
+    fn empty() -> MvdFrame<'static> {
         MvdFrame {
             messages: vec!(),
+            hidden: vec!(),
             frame: 0,
             time: 0.0,
             last: MvdTarget { ..Default::default() }
@@ -57,8 +97,8 @@ impl MvdFrame {
     }
 }
 
-impl Mvd {
-    pub fn empty() -> Mvd {
+impl<'a> Mvd<'a> {
+    pub fn empty() -> Mvd<'a> {
         Mvd {
             size:0,
             message: Message::empty(),
@@ -68,19 +108,21 @@ impl Mvd {
             time: 0.0,
 #[cfg(feature = "trace")]
             trace: false,
+            reader: None,
+            failed: false,
         }
     }
 
-    pub fn new(buffer: Vec<u8>,
+    pub fn new(buffer: impl Into<Cow<'a, [u8]>>,
 #[cfg(feature = "ascii_strings")]
                maybe_ascii_converter: Option<AsciiConverter>,
 #[cfg(feature = "trace")]
                trace: bool,
-               ) -> Result<Mvd, std::io::Error> {
-
-        let buffer_heap = Box::new(buffer.clone());
+               ) -> Result<Mvd<'a>, std::io::Error> {
+        let buffer = buffer.into();
+        let size = buffer.len();
 
-        let mut message = Message::new(buffer_heap, 0, buffer.len(), false, MessageFlags::new_empty(),
+        let mut message = Message::new(buffer, 0, size, false, MessageFlags::new_empty(),
 #[cfg(feature = "ascii_strings")]
         maybe_ascii_converter,
         MessageType::Mvd);
@@ -91,7 +133,7 @@ impl Mvd {
         }
 
         Ok(Mvd {
-            size:buffer.len(),
+            size,
             message,
             finished: false,
             last: MvdTarget{ ..Default::default() },
@@ -99,10 +141,105 @@ impl Mvd {
             time: 0.0,
 #[cfg(feature = "trace")]
             trace,
+            reader: None,
+            failed: false,
         })
     }
 
-    pub fn parse_frame(&mut self) -> Result<Box<MvdFrame>, MvdParseError> {
+    /// parses frames while reading them from `reader`, only whole frames are kept in memory
+    ///
+    /// if a frame is not complete yet [`MvdParseError::Incomplete`] is returned and
+    /// `parse_frame` can be called again once more data is available
+    pub fn from_reader(reader: impl Read + 'a,
+#[cfg(feature = "ascii_strings")]
+               maybe_ascii_converter: Option<AsciiConverter>,
+#[cfg(feature = "trace")]
+               trace: bool,
+               ) -> Mvd<'a> {
+        let mut message = Message::new(Vec::new(), 0, 0, false, MessageFlags::new_empty(),
+#[cfg(feature = "ascii_strings")]
+        maybe_ascii_converter,
+        MessageType::Mvd);
+
+        #[cfg(feature = "trace")]
+        {
+            message.trace.enabled = trace;
+        }
+
+        Mvd {
+            size: 0,
+            message,
+            finished: false,
+            last: MvdTarget{ ..Default::default() },
+            frame: 0,
+            time: 0.0,
+#[cfg(feature = "trace")]
+            trace,
+            reader: Some(MvdReader(Box::new(reader))),
+            failed: false,
+        }
+    }
+
+    /// size of the next frame, None if not enough data is available to tell
+    fn next_frame_size(&self) -> Option<usize> {
+        let data = &self.message.buffer[self.message.start + self.message.position..self.message.start + self.message.length];
+        let read_u32 = |offset: usize| -> Option<usize> {
+            let bytes = data.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+        };
+        let cmd = *data.get(1)?;
+        match DemoCommand::try_from(cmd & 7) {
+            Ok(DemoCommand::Set) => Some(2 + 8),
+            Ok(DemoCommand::Multiple) => Some(2 + 4 + 4 + read_u32(6)?),
+            Ok(DemoCommand::Command) | Err(_) => Some(2),
+            Ok(_) => Some(2 + 4 + read_u32(2)?),
+        }
+    }
+
+    /// drops the already parsed data and appends what the reader has
+    fn fill(&mut self) -> Result<usize, MvdParseError> {
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => return Ok(0),
+        };
+        let buffer = self.message.buffer.to_mut();
+        buffer.drain(..self.message.start + self.message.position);
+        self.message.start = 0;
+        self.message.position = 0;
+
+        let mut chunk = vec![0; STREAM_READ_SIZE];
+        let read = loop {
+            match reader.0.read(&mut chunk) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break 0,
+                Err(e) => return Err(MvdParseError::Io(e)),
+            }
+        };
+        buffer.extend_from_slice(&chunk[..read]);
+        self.message.length = buffer.len();
+        self.size = buffer.len();
+        Ok(read)
+    }
+
+    /// makes sure the next frame is completely available
+    fn ensure_frame(&mut self) -> Result<(), MvdParseError> {
+        loop {
+            let available = self.message.length - self.message.position;
+            let needed = self.next_frame_size();
+            match needed {
+                Some(size) if size <= available => return Ok(()),
+                _ => {
+                    if self.fill()? == 0 {
+                        return Err(MvdParseError::Incomplete(needed.unwrap_or(available + 1), available));
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn parse_frame(&mut self) -> Result<Box<MvdFrame<'a>>, MvdParseError> {
+        self.ensure_frame()?;
         let mut frame = Box::new(MvdFrame::empty());
         frame.frame = self.frame;
         self.frame += 1;
@@ -123,7 +260,6 @@ impl Mvd {
         if msg_type == DemoCommand::Command {
             return Err(MvdParseError::QwdCommand)
         }
-        frame.last.command = msg_type;
         if msg_type >= DemoCommand::Multiple && msg_type <= DemoCommand::All {
             match msg_type {
                 DemoCommand::Multiple => {
@@ -148,6 +284,7 @@ impl Mvd {
                 DemoCommand::Empty=> {
                 }
                 DemoCommand::Set => {
+                    frame.last.command = msg_type;
                     // incoming
                     trace_annotate!(self.message, "sequence");
                     let _ = self.message.read_u32(false);
@@ -161,6 +298,10 @@ impl Mvd {
                 }
             }
         }
+        frame.last = MvdTarget{
+            to: self.last.to,
+            command: msg_type,
+        };
         let mut loop_read_packet = true;
         while  loop_read_packet {
             loop_read_packet = self.read_packet(&mut frame)?;
@@ -169,7 +310,7 @@ impl Mvd {
         Ok(frame)
     }
 
-    pub fn read_packet(&mut self, frame: &mut Box<MvdFrame>) -> Result<bool, MvdParseError> {
+    pub fn read_packet(&mut self, frame: &mut Box<MvdFrame<'a>>) -> Result<bool, MvdParseError> {
         trace_start!(self.message, false);
         trace_annotate!(self.message, "size");
         let size = self.message.read_u32(false)? as usize;
@@ -190,7 +331,7 @@ impl Mvd {
         */
 
         if self.last.command == DemoCommand::Multiple && self.last.to == 0 {
-            self.message.position += size;
+            frame.hidden = MvdHiddenMessage::read_block(&mut self.message, size)?;
             return Ok(false)
         }
 
@@ -223,6 +364,7 @@ impl Mvd {
                     frame.messages.push(ServerMessage::Serverdata(r.clone()));
                     self.message.flags.fte_protocol_extensions = r.fte_protocol_extension;
                     self.message.flags.fte_protocol_extensions_2 = r.fte_protocol_extension_2;
+                    self.message.flags.mvd_protocol_extension = r.mvd_protocol_extension;
                 }
                 _ => {
                     frame.messages.push(ret);
@@ -238,3 +380,179 @@ impl Mvd {
         Ok(false)
     }
 }
+
+/// yields frames until EndOfDemo or until the data runs out
+///
+/// after an error `None` is returned once, a streaming [`Mvd`] can then be iterated again when more
+/// data arrived, corrupt data ends the iteration
+impl<'a> Iterator for Mvd<'a> {
+    type Item = Result<MvdFrame<'a>, MvdParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        if self.failed {
+            self.failed = false;
+            return None;
+        }
+        match self.parse_frame() {
+            Ok(frame) => Some(Ok(*frame)),
+            // the data ended between two frames
+            Err(MvdParseError::Incomplete(_, 0)) => None,
+            Err(e) => {
+                if e.is_incomplete() {
+                    self.failed = true;
+                } else {
+                    self.finished = true;
+                }
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+#[cfg(feature = "state")]
+impl<'a> Mvd<'a> {
+    /// applies every frame to a [`State`] and yields it together with the frame
+    pub fn with_state(self) -> MvdStateFrames<'a> {
+        self.with_state_from(State::new())
+    }
+
+    /// same as [`Mvd::with_state`] but continues with `state`, e.g. the one returned by [`Mvd::seek_to_time`]
+    pub fn with_state_from(self, state: State) -> MvdStateFrames<'a> {
+        MvdStateFrames {
+            mvd: self,
+            state: Rc::new(state),
+        }
+    }
+}
+
+/// iterator returned by [`Mvd::with_state`]
+///
+/// the state is only copied if a previously yielded one is still kept around
+#[cfg(feature = "state")]
+#[derive(Debug)]
+pub struct MvdStateFrames<'a> {
+    pub mvd: Mvd<'a>,
+    state: Rc<State>,
+}
+
+#[cfg(feature = "state")]
+impl MvdStateFrames<'_> {
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+}
+
+#[cfg(feature = "state")]
+impl<'a> Iterator for MvdStateFrames<'a> {
+    type Item = Result<(MvdFrame<'a>, Rc<State>), MvdParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.mvd.next()? {
+            Ok(frame) => {
+                if let Err(e) = Rc::make_mut(&mut self.state).apply_messages_mvd(&frame.messages, frame.last.clone()) {
+                    return Some(Err(e.into()));
+                }
+                Some(Ok((frame, Rc::clone(&self.state))))
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{Mvd, MvdFrame, MvdTarget, MvdWriter};
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    /// an [`Mvd`] of `buffer` without ascii converter and trace
+    pub(crate) fn new_mvd(buffer: Vec<u8>) -> Mvd<'static> {
+        Mvd::new(buffer,
+#[cfg(feature = "ascii_strings")]
+            None,
+#[cfg(feature = "trace")]
+            false,
+            ).unwrap()
+    }
+
+    /// all frames of `buffer`
+    pub(crate) fn parse(buffer: Vec<u8>) -> Vec<MvdFrame<'static>> {
+        new_mvd(buffer).map(|frame| frame.unwrap()).collect()
+    }
+
+    // hands out a few bytes at a time of a buffer that can still grow
+    struct GrowingReader<'b> {
+        data: &'b std::cell::RefCell<Vec<u8>>,
+        position: usize,
+    }
+
+    impl std::io::Read for GrowingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let data = self.data.borrow();
+            let length = (data.len() - self.position).min(buf.len()).min(7);
+            buf[..length].copy_from_slice(&data[self.position..self.position + length]);
+            self.position += length;
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn mvd_stream() {
+        let ascii_converter = AsciiConverter::new();
+        let mut writer = MvdWriter::new();
+        for i in 0..20 {
+            writer.write_messages(i as f64 * 0.1, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Print(Print{ from: 2, message: ascii_converter.convert_to_stringbyte(format!("line {}\n", i)) }),
+            ]).unwrap();
+        }
+        let buffer = writer.finish().unwrap();
+        let expected = parse(buffer.clone());
+
+        let data = std::cell::RefCell::new(buffer[..buffer.len() / 2].to_vec());
+        let mut mvd = Mvd::from_reader(GrowingReader{ data: &data, position: 0 },
+#[cfg(feature = "ascii_strings")]
+            None,
+#[cfg(feature = "trace")]
+            false,
+            );
+        let mut frames = vec![];
+        loop {
+            match mvd.parse_frame() {
+                Ok(frame) => frames.push(*frame),
+                Err(e) => {
+                    assert!(e.is_incomplete());
+                    break;
+                },
+            }
+        }
+        assert!(frames.len() < expected.len());
+        // a frame that was incomplete is parsed once the rest arrives
+        data.borrow_mut().extend_from_slice(&buffer[buffer.len() / 2..]);
+        while !mvd.finished {
+            frames.push(*mvd.parse_frame().unwrap());
+        }
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn mvd_iterator() {
+        let mut writer = MvdWriter::new();
+        for i in 0..5 {
+            writer.write_messages(i as f64 * 0.1, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+                ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: i }),
+            ]).unwrap();
+        }
+        let buffer = writer.finish().unwrap();
+        let frames = new_mvd(buffer.clone()).collect::<Result<Vec<MvdFrame>, _>>().unwrap();
+        assert_eq!(frames, parse(buffer.clone()));
+
+        let frags: Vec<i16> = new_mvd(buffer)
+            .with_state()
+            .map(|r| r.unwrap().1.players[&1].frags)
+            .collect();
+        assert_eq!(frags, vec![0, 1, 2, 3, 4, 4]);
+    }
+}
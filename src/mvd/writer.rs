@@ -0,0 +1,378 @@
+use serde::Serialize;
+use crate::protocol::message::Message;
+use crate::protocol::message::MessageFlags;
+use crate::protocol::message::MessageType;
+use crate::protocol::message::errors::MessageError;
+use crate::protocol::types::*;
+use crate::mvd::{MvdFrame, MvdHiddenMessage, MvdTarget};
+
+#[cfg(feature = "state")]
+use crate::state::State;
+#[cfg(feature = "state")]
+use crate::utils::ascii_converter::AsciiConverter;
+
+/// writes [`MvdFrame`]s into the mvd file format
+#[derive(Serialize, Debug)]
+pub struct MvdWriter {
+    pub message: Message<'static>,
+    pub time: f64,
+    pub finished: bool,
+    gamestate_written: bool,
+}
+
+impl Default for MvdWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MvdWriter {
+    pub fn new() -> MvdWriter {
+        let mut message = Message::empty();
+        message.r#type = MessageType::Mvd;
+        message.flags = MessageFlags::new_empty();
+        MvdWriter {
+            message,
+            time: 0.0,
+            finished: false,
+            gamestate_written: false,
+        }
+    }
+
+    /// advances the demo time to `time`, demo time is stored in ms steps of at most 255
+    fn write_demo_time(&mut self, time: f64) -> usize {
+        let mut size: usize = 0;
+        let mut delta = ((time - self.time) * 1000.0).round() as i64;
+        if delta < 0 {
+            delta = 0;
+        }
+        while delta > 255 {
+            // an empty block to all, so the time can keep running
+            size += self.message.write_u8(255_u8);
+            size += self.message.write_u8(DemoCommand::All as u8);
+            size += self.message.write_u32(0_u32);
+            self.time += 255.0 * 0.001;
+            delta -= 255;
+        }
+        size += self.message.write_u8(delta as u8);
+        self.time += delta as f64 * 0.001;
+        size
+    }
+
+    fn write_target(&mut self, target: &MvdTarget) -> Result<usize, MessageError> {
+        let size = match target.command {
+            DemoCommand::Multiple => {
+                self.message.write_u8(DemoCommand::Multiple as u8) + self.message.write_u32(target.to)
+            },
+            DemoCommand::Single | DemoCommand::Stats => {
+                self.message.write_u8(target.command as u8 | (target.to << 3) as u8)
+            },
+            DemoCommand::All | DemoCommand::Read | DemoCommand::Set => {
+                self.message.write_u8(target.command as u8)
+            },
+            DemoCommand::Command | DemoCommand::Empty => {
+                return Err(MessageError::StringError(format!("cannot write demo command {}", target.command)))
+            },
+        };
+        Ok(size)
+    }
+
+    /// writes the messages as a size prefixed block
+    fn write_block(&mut self, messages: &[ServerMessage]) -> Result<usize, MessageError> {
+        let size_position = self.message.position;
+        self.message.write_u32(0_u32);
+        let mut size: usize = 0;
+        for message in messages {
+            size += message.write(&mut self.message)?;
+            match message {
+                ServerMessage::Serverdata(serverdata) => {
+                    self.message.flags.fte_protocol_extensions = serverdata.fte_protocol_extension;
+                    self.message.flags.fte_protocol_extensions_2 = serverdata.fte_protocol_extension_2;
+                    self.message.flags.mvd_protocol_extension = serverdata.mvd_protocol_extension;
+                },
+                ServerMessage::Disconnect(_) => {
+                    self.finished = true;
+                },
+                _ => {},
+            }
+        }
+        self.message.replace_at_position((size as u32).to_le_bytes(), size_position)?;
+        Ok(size + 4)
+    }
+
+    /// writes the hidden messages as a size prefixed block
+    fn write_hidden_block(&mut self, hidden: &[MvdHiddenMessage]) -> Result<usize, MessageError> {
+        let size_position = self.message.position;
+        self.message.write_u32(0_u32);
+        let size: usize = hidden.iter().map(|h| h.write(&mut self.message)).sum();
+        self.message.replace_at_position((size as u32).to_le_bytes(), size_position)?;
+        Ok(size + 4)
+    }
+
+    /// writes a single [`MvdFrame`], the frames `time` and `last` target are used for the framing
+    pub fn write_frame(&mut self, frame: &MvdFrame) -> Result<usize, MessageError> {
+        let mut size = self.write_demo_time(frame.time);
+        size += self.write_target(&frame.last)?;
+        if frame.last.command == DemoCommand::Set {
+            size += self.message.write_u32(0_u32);
+            size += self.message.write_u32(0_u32);
+            return Ok(size);
+        }
+        if frame.hidden.is_empty() {
+            size += self.write_block(&frame.messages)?;
+        } else {
+            size += self.write_hidden_block(&frame.hidden)?;
+        }
+        Ok(size)
+    }
+
+    /// writes the messages to `target` at `time`
+    pub fn write_messages(&mut self, time: f64, target: MvdTarget, messages: Vec<ServerMessage>) -> Result<usize, MessageError> {
+        self.write_frame(&MvdFrame {
+            messages,
+            hidden: vec![],
+            frame: 0,
+            time,
+            last: target,
+        })
+    }
+
+    /// writes the EndOfDemo marker, if it wasnt already written
+    pub fn write_end_of_demo(&mut self, time: f64) -> Result<usize, MessageError> {
+        if self.finished {
+            return Ok(0);
+        }
+        self.write_messages(time, MvdTarget{ to: 0, command: DemoCommand::All }, vec![ServerMessage::Disconnect(Disconnect{})])
+    }
+
+    /// finishes the demo and returns the written bytes
+    pub fn finish(mut self) -> Result<Vec<u8>, MessageError> {
+        let time = self.time;
+        self.write_end_of_demo(time)?;
+        Ok(self.message.buffer.into_owned())
+    }
+
+    /// writes a [`State`] snapshot, the first call writes the gamestate the following
+    /// calls only write the player and entity updates
+    #[cfg(feature = "state")]
+    pub fn write_state(&mut self, state: &State, time: f64) -> Result<usize, MessageError> {
+        let mut size: usize = 0;
+        let all = MvdTarget{ to: 0, command: DemoCommand::All };
+        if !self.gamestate_written {
+            size += self.write_messages(time, all.clone(), gamestate_messages(state))?;
+            self.gamestate_written = true;
+        }
+        size += self.write_messages(time, all, state_messages(state))?;
+        for (player_number, player) in sorted_players(state) {
+            let messages: Vec<ServerMessage> = player.stats.iter().enumerate()
+                .filter(|(_, value)| **value != 0)
+                .map(|(stat, value)| ServerMessage::Updatestatlong(Updatestatlong{ stat: stat as u8, value: *value }))
+                .collect();
+            if messages.is_empty() {
+                continue;
+            }
+            size += self.write_messages(time, MvdTarget{ to: player_number as u32, command: DemoCommand::Stats }, messages)?;
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(feature = "state")]
+fn sorted_players(state: &State) -> Vec<(u16, &crate::state::Player)> {
+    let mut players: Vec<(u16, &crate::state::Player)> = state.players.iter().map(|(k, v)| (*k, v)).collect();
+    players.sort_by_key(|(k, _)| *k);
+    players
+}
+
+/// messages needed to bring a client to the static part of the [`State`]
+#[cfg(feature = "state")]
+pub(crate) fn gamestate_messages(state: &State) -> Vec<ServerMessage<'static>> {
+    let ascii_converter = AsciiConverter::new();
+    let mut serverinfo = b"fullserverinfo \"".to_vec();
+    serverinfo.extend(state.serverinfo.info.clone().as_bytes());
+    serverinfo.extend(b"\"\n");
+    let mut messages = vec![
+        ServerMessage::Serverdata(state.serverdata.clone()),
+        ServerMessage::Stufftext(Stufftext{ text: ascii_converter.convert_to_stringbyte(serverinfo) }),
+        ServerMessage::Soundlist(Soundlist{ start: 0, sounds: state.sounds.clone(), offset: 0 }),
+        ServerMessage::Modellist(Modellist{ start: 0, models: state.models.clone(), offset: 0 }),
+    ];
+    for static_entity in &state.static_entities {
+        messages.push(ServerMessage::Spawnstatic(*static_entity));
+    }
+    for static_sound in &state.static_sounds {
+        messages.push(ServerMessage::Spawnstaticsound(static_sound.clone()));
+    }
+    let mut baselines: Vec<_> = state.baseline_entities.iter().collect();
+    baselines.sort_by_key(|(k, _)| **k);
+    for (index, entity) in baselines {
+        messages.push(ServerMessage::Spawnbaseline(Spawnbaseline{
+            index: *index,
+            model_index: entity.model as u8,
+            model_frame: entity.frame,
+            colormap: entity.colormap,
+            skinnum: entity.skinnum,
+            origin: entity.origin,
+            angle: entity.angle,
+        }));
+    }
+    let mut lightstyles: Vec<_> = state.lightstyles.iter().collect();
+    lightstyles.sort_by_key(|(k, _)| **k);
+    for (index, style) in lightstyles {
+        messages.push(ServerMessage::Lightstyle(Lightstyle{ index: *index, style: style.clone() }));
+    }
+    messages.push(ServerMessage::Cdtrack(Cdtrack{ track: state.cdtrack }));
+    for (player_number, player) in sorted_players(state) {
+        messages.push(ServerMessage::Updateuserinfo(Updateuserinfo{
+            player_number: player_number as u8,
+            uid: player.uid,
+            userinfo: ascii_converter.convert_to_stringbyte(player.userinfo.clone().as_bytes()),
+        }));
+        messages.push(ServerMessage::Updateentertime(Updateentertime{
+            player_number: player_number as u8,
+            entertime: player.entertime,
+        }));
+    }
+    messages
+}
+
+/// messages needed to bring a client to the changing part of the [`State`]
+#[cfg(feature = "state")]
+pub(crate) fn state_messages(state: &State) -> Vec<ServerMessage<'static>> {
+    let mut messages = vec![];
+    for (player_number, player) in sorted_players(state) {
+        messages.push(ServerMessage::Updatefrags(Updatefrags{
+            player_number: player_number as u8,
+            frags: player.frags,
+        }));
+        messages.push(ServerMessage::Updateping(Updateping{
+            player_number: player_number as u8,
+            ping: player.ping,
+        }));
+        messages.push(ServerMessage::Updatepl(Updatepl{
+            player_number: player_number as u8,
+            pl: player.pl,
+        }));
+        let mut flags = DfTypes::ORIGIN | DfTypes::ORIGIN2 | DfTypes::ORIGIN3 |
+            DfTypes::ANGLE | DfTypes::ANGLE2 | DfTypes::ANGLE3 |
+            DfTypes::MODEL | DfTypes::SKINNUM | DfTypes::EFFECTS | DfTypes::WEAPONFRAME;
+        flags.set(DfTypes::DEAD, player.dead);
+        flags.set(DfTypes::GIB, player.gib);
+        messages.push(ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+            player_number: player_number as u8,
+            flags,
+            frame: player.frame,
+            origin: Some(CoordinateVectorOption{ x: Some(player.origin.x), y: Some(player.origin.y), z: Some(player.origin.z) }),
+            angle: Some(AngleVectorOption{ x: Some(player.angle.x), y: Some(player.angle.y), z: Some(player.angle.z) }),
+            model: Some(player.model),
+            skinnum: Some(player.skinnum),
+            effects: Some(player.effects),
+            weaponframe: Some(player.weaponframe),
+        })));
+    }
+    let mut entities: Vec<_> = state.entities.values().collect();
+    entities.sort_by_key(|e| e.index);
+    let bits = UpdateTypes::MOREBITS | UpdateTypes::ORIGIN1 | UpdateTypes::ORIGIN2 | UpdateTypes::ORIGIN3 |
+        UpdateTypes::ANGLE1 | UpdateTypes::ANGLE2 | UpdateTypes::ANGLE3 | UpdateTypes::FRAME |
+        UpdateTypes::MODEL | UpdateTypes::COLORMAP | UpdateTypes::SKIN | UpdateTypes::EFFECTS;
+    messages.push(ServerMessage::Packetentities(Packetentities{
+        entities: entities.iter().map(|e| Packetentity{
+            entity_index: e.index,
+            bits: bits.bits(),
+            model: Some(e.model),
+            frame: Some(e.frame),
+            colormap: Some(e.colormap),
+            skin: Some(e.skinnum),
+            effects: Some(e.effects),
+            origin: Some(CoordinateVectorOption{ x: Some(e.origin.x), y: Some(e.origin.y), z: Some(e.origin.z) }),
+            angle: Some(AngleVectorOption{ x: Some(e.angle.x), y: Some(e.angle.y), z: Some(e.angle.z) }),
+            ..Default::default()
+        }).collect(),
+    }));
+    if !state.projectiles.is_empty() {
+        messages.push(ServerMessage::Nails2(Nails2{
+            projectiles: state.projectiles.clone(),
+        }));
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::{MvdTarget, MvdWriter};
+    use crate::mvd::tests::parse;
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    #[test]
+    fn mvd_round_trip() {
+        let ascii_converter = AsciiConverter::new();
+        let mut writer = MvdWriter::new();
+        writer.write_messages(0.0, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+            ServerMessage::Serverdata(Serverdata{
+                protocol: ProtocolVersion::Standard,
+                servercount: 5,
+                demotime: 1.5,
+                gamedir: ascii_converter.convert_to_stringbyte("qw"),
+                map: ascii_converter.convert_to_stringbyte("dm3"),
+                ..Default::default()
+            }),
+            ServerMessage::Modellist(Modellist{
+                start: 0,
+                models: vec![ascii_converter.convert_to_stringbyte("maps/dm3.bsp"), ascii_converter.convert_to_stringbyte("progs/player.mdl")],
+                offset: 0,
+            }),
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: -2 }),
+        ]).unwrap();
+        writer.write_messages(0.013, MvdTarget{ to: 3, command: DemoCommand::Stats }, vec![
+            ServerMessage::Updatestat(Updatestat{ stat: 1, value: 100 }),
+        ]).unwrap();
+        writer.write_messages(0.5, MvdTarget{ to: 5, command: DemoCommand::Multiple }, vec![
+            ServerMessage::Print(Print{ from: 2, message: ascii_converter.convert_to_stringbyte("hello\n") }),
+        ]).unwrap();
+        writer.write_messages(0.6, MvdTarget{ to: 5, command: DemoCommand::Read }, vec![]).unwrap();
+        writer.write_messages(0.7, MvdTarget{ to: 2, command: DemoCommand::Single }, vec![
+            ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+                player_number: 2,
+                flags: DfTypes::ORIGIN | DfTypes::ORIGIN3 | DfTypes::ANGLE2 | DfTypes::MODEL,
+                frame: 4,
+                origin: Some(CoordinateVectorOption{ x: Some(128.5), y: None, z: Some(-24.0) }),
+                angle: Some(AngleVectorOption{ x: None, y: Some(90.0), z: None }),
+                model: Some(3),
+                skinnum: None,
+                effects: None,
+                weaponframe: None,
+            })),
+        ]).unwrap();
+        writer.write_messages(1.2, MvdTarget{ to: 0, command: DemoCommand::All }, vec![
+            ServerMessage::Deltapacketentities(Deltapacketentities{
+                from: 1,
+                entities: vec![Packetentity{
+                    entity_index: 42,
+                    bits: (UpdateTypes::ORIGIN1 | UpdateTypes::ORIGIN2 | UpdateTypes::MOREBITS | UpdateTypes::ANGLE3).bits(),
+                    origin: Some(CoordinateVectorOption{ x: Some(1.0), y: Some(-2.0), z: None }),
+                    angle: Some(AngleVectorOption{ x: None, y: None, z: Some(180.0) }),
+                    ..Default::default()
+                }],
+            }),
+        ]).unwrap();
+        let buffer = writer.finish().unwrap();
+
+        let frames = parse(buffer.clone());
+        // gaps longer than 255ms get an empty frame in between
+        assert_eq!(frames.len(), 9);
+        assert_eq!(frames[1].last, MvdTarget{ to: 3, command: DemoCommand::Stats });
+        assert_eq!(frames[3].last, MvdTarget{ to: 5, command: DemoCommand::Multiple });
+        assert_eq!(frames[4].last, MvdTarget{ to: 5, command: DemoCommand::Read });
+        assert!((frames[7].time - 1.2).abs() < 0.0001);
+
+        let mut writer = MvdWriter::new();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        assert!(writer.finished);
+        let rewritten = writer.finish().unwrap();
+        assert_eq!(buffer, rewritten);
+        assert_eq!(frames, parse(rewritten));
+    }
+}
@@ -1,10 +1,13 @@
+use std::time::Instant;
 use thiserror::Error;
 use crate::network::channel::Channel;
+use crate::network::connection::download::FileDownload;
 use crate::protocol::message::Message;
 use crate::protocol::message::MessageFlags;
 use crate::protocol::message::MessageType;
 use crate::protocol::types::{Packet, ProtocolVersion, ServerMessage, ClientServer, Serverdata, DeltaUserCommand};
 use crate::utils::userinfo::Userinfo;
+use crate::state::{ServerInfo, State};
 
 use crate::crc::generate_checksum;
 
@@ -39,15 +42,22 @@ pub struct Client {
     pub state: ClientConnectionState,
     pub userinfo: Userinfo,
     pub protocol: MessageFlags,
-    pub serverdata: Serverdata,
+    pub serverdata: Serverdata<'static>,
+    pub serverinfo: ServerInfo,
+    /// updated from every packet, errors end up in its `warnings`
+    pub game_state: State,
     pub prespawn_send: bool,
     pub map_crc: u32,
+    pub download: Option<FileDownload>,
+    pub download_queue: Vec<String>,
 }
 
 #[derive(Default, Serialize)]
 pub struct ClientStatus {
-    pub packet: Option<Packet>,
+    pub packet: Option<Packet<'static>>,
     pub response: Option<Vec<u8>>,
+    /// a download that finished or failed with this packet
+    pub download: Option<FileDownload>,
 }
 
 pub fn print_seq(out: bool, first: u32, second: u32) {
@@ -63,11 +73,14 @@ pub fn print_seq(out: bool, first: u32, second: u32) {
 
 impl Client {
     pub fn new(ip: String, ascii_converter: AsciiConverter) -> Client {
+        let mut game_state = State::new_with_ascii_conveter(ascii_converter.clone());
+        game_state.lenient = true;
         let mut userinfo = Userinfo::new_with_ascii_converter(ascii_converter);
         userinfo.update_from_string("*rust_quakeworld", env!("CARGO_PKG_VERSION"));
         Client {
             ip,
             userinfo,
+            game_state,
             ..Default::default()
         }
     }
@@ -98,7 +111,7 @@ impl Client {
                 msec: Some(0),
                 ..Default::default()});
 
-        let crc = generate_checksum(message.clone(), position+1, message.position, self.channel.outgoing.sequence);
+        let crc = generate_checksum(&message.buffer, position+1, message.position, self.channel.outgoing.sequence);
         message.replace_at_position([(crc & 0xff) as u8], position)?;
         Ok(())
     }
@@ -108,24 +121,29 @@ impl Client {
         if self.state == ClientConnectionState::ConnectionNegotiatonChallengeSend {
             return Ok(ClientStatus {
                 response: Some(self.get_challenge()),
-                packet: None});
+                ..Default::default()});
         } else if self.state != ClientConnectionState::Connected {
-            return Ok(ClientStatus { 
-                response: None ,
-                packet: None });
+            return Ok(ClientStatus::default());
         }
         let (out, ack) = self.channel.unreliable();
         message.write_u32(out);
         message.write_u32(ack);
         message.write_u16(self.local_port);
+        if let Some(download) = &mut self.download {
+            for command in download.resend(Instant::now()) {
+                message.write_client_command_string(command);
+            }
+        }
+        self.start_download(&mut message);
         self.write_empty_move_cmd(&mut message)?;
         Ok(ClientStatus {
-            response: Some(*message.buffer.clone()),
-            packet: None})
+            response: Some(message.buffer.to_vec()),
+            ..Default::default()})
     }
 
     pub fn handle_packet(&mut self, packet: Vec<u8>) -> Result<ClientStatus, Box<dyn std::error::Error>> {
-        let mut message = Message::new(Box::new(packet.clone()), 0, packet.len(), false, self.protocol, None, MessageType::Connection);
+        let length = packet.len();
+        let mut message = Message::new(packet, 0, length, false, self.protocol, None, MessageType::Connection);
         message.trace.enabled = true;
         let p = match message.read_packet() {
             Ok(p) => p,
@@ -167,7 +185,7 @@ impl Client {
                 msg.extend(b"\n");
                 return Ok(ClientStatus{
                     response: Some(msg),
-                    packet: None});
+                    ..Default::default()});
             },
             Packet::ConnectionLessServerConnection => {
                 let mut message = Message::empty();
@@ -181,18 +199,20 @@ impl Client {
                 message.write_u8(ClientServer::Nop as u8);
                 self.write_empty_move_cmd(&mut message)?;
                 return Ok(ClientStatus{
-                    response: Some(*message.buffer.clone()),
-                    packet: None});
+                    response: Some(message.buffer.to_vec()),
+                    ..Default::default()});
             },
             Packet::Connected(p)=> {
                 self.state = ClientConnectionState::Connected;
                 self.channel.recieved(p.sequence, p.sequence_ack);
+                self.game_state.apply_packet(&p)?;
                 let mut message = Message::empty();
 
                 let (out, ack) = self.channel.unreliable();
                 message.write_u32(out);
                 message.write_u32(ack);
                 message.write_u16(self.local_port);
+                let mut finished_download = None;
                 for server_message in &p.messages {
                     match server_message {
                         ServerMessage::Soundlist(soundlist) => {
@@ -253,6 +273,7 @@ impl Client {
                                 }
                                 message.write_client_command_string(format!("spawn {} {}", vec[2], vec[3]));
                             } else if stufftext.text.string.starts_with("fullserverinfo") {
+                                self.serverinfo.update_from_stufftext(&stufftext.text.bytes);
                             } else if stufftext.text.string.starts_with("skins") {
                                 message.write_client_command_string(format!("begin {}", self.serverdata.servercount));
                             }
@@ -264,19 +285,49 @@ impl Client {
                             self.protocol.fte_protocol_extensions_2 = serverdata.fte_protocol_extension_2;
                             self.protocol.mvd_protocol_extension = serverdata.mvd_protocol_extension;
                         },
+                        ServerMessage::Serverinfo(serverinfo) => {
+                            self.serverinfo.update_key_value(&serverinfo.key, &serverinfo.value);
+                        },
+                        ServerMessage::Download(data) => {
+                            if let Some(download) = &mut self.download {
+                                for command in download.handle(data) {
+                                    message.write_client_command_string(command);
+                                }
+                                if download.is_done() {
+                                    finished_download = self.download.take();
+                                }
+                            }
+                        },
                         _ => {},
                     }
                 }
+                self.start_download(&mut message);
                 self.write_empty_move_cmd(&mut message)?;
                 return Ok(ClientStatus{
-                    response: Some(*message.buffer.clone()),
-                    packet: Some(Packet::Connected(p))});
+                    response: Some(message.buffer.to_vec()),
+                    packet: Some(Packet::Connected(p)),
+                    download: finished_download});
             },
             _ => {},
         }
         Err(Box::new(ClientError::UnhandledPacket))
     }
 
+    /// queue a file to be downloaded from the server, the finished file is returned in [`ClientStatus`]
+    pub fn download(&mut self, name: impl Into<String>) {
+        self.download_queue.push(name.into());
+    }
+
+    // request the next queued file if no download is running
+    fn start_download(&mut self, message: &mut Message) {
+        if self.state != ClientConnectionState::Connected || self.download.is_some() || self.download_queue.is_empty() {
+            return;
+        }
+        let name = self.download_queue.remove(0);
+        message.write_client_command_string(format!("download {}", name));
+        self.download = Some(FileDownload::new(name));
+    }
+
     fn get_challenge(&mut self) -> Vec<u8> {
         self.state = ClientConnectionState::ConnectionNegotiatonChallengeSend;
         let mut m: Vec<u8> = Vec::from([255, 255, 255, 255]);
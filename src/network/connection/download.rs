@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::protocol::types::{Download, DOWNLOAD_CHUNK_SIZE};
+
+/// a requested chunk that didn't arrive in this time is requested again
+pub const CHUNK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Default, Serialize, PartialEq, Eq, Debug, Clone)]
+pub enum DownloadState {
+    #[default] Requested,
+    Receiving,
+    Finished,
+    Failed,
+}
+
+/// keeps track of a file requested with `download <file>`
+#[derive(Default, Serialize, Debug, Clone)]
+pub struct FileDownload {
+    pub name: String,
+    pub state: DownloadState,
+    pub chunked: bool,
+    pub size: Option<usize>,
+    pub percent: u8,
+    pub data: Vec<u8>,
+    chunks: Vec<bool>,
+    /// when each chunk was last requested
+    #[serde(skip)]
+    requested: Vec<Option<Instant>>,
+}
+
+impl FileDownload {
+    pub fn new(name: impl Into<String>) -> FileDownload {
+        FileDownload {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == DownloadState::Finished || self.state == DownloadState::Failed
+    }
+
+    /// applies a [`Download`] message, returns the commands that need to be send to the server
+    pub fn handle(&mut self, download: &Download) -> Vec<String> {
+        self.handle_at(download, Instant::now())
+    }
+
+    /// [`FileDownload::handle`] with the time the message was received
+    pub fn handle_at(&mut self, download: &Download, now: Instant) -> Vec<String> {
+        if self.is_done() {
+            return vec![];
+        }
+        match download.chunk {
+            Some(chunk) => self.handle_chunk(chunk, download, now),
+            None => self.handle_block(download),
+        }
+    }
+
+    /// requests the chunks again that didn't arrive in [`CHUNK_TIMEOUT`]
+    pub fn resend(&mut self, now: Instant) -> Vec<String> {
+        if self.is_done() || !self.chunked {
+            return vec![];
+        }
+        self.request_chunks(now)
+    }
+
+    fn handle_block(&mut self, download: &Download) -> Vec<String> {
+        match download.size {
+            -1 => {
+                self.state = DownloadState::Failed;
+                vec![]
+            },
+            -2 => {
+                // the server continues with the data on its own
+                if let Some(name) = &download.name {
+                    self.name = String::from_utf8_lossy(&name.bytes).to_string();
+                }
+                vec![]
+            },
+            _ => {
+                self.state = DownloadState::Receiving;
+                self.data.extend_from_slice(&download.data);
+                self.percent = download.percent;
+                if download.percent == 100 {
+                    self.size = Some(self.data.len());
+                    self.state = DownloadState::Finished;
+                    return vec![];
+                }
+                vec!["nextdl".to_string()]
+            },
+        }
+    }
+
+    fn handle_chunk(&mut self, chunk: i32, download: &Download, now: Instant) -> Vec<String> {
+        self.chunked = true;
+        if chunk < 0 {
+            if download.size < 0 {
+                self.state = DownloadState::Failed;
+                return vec![];
+            }
+            let size = download.size as usize;
+            self.size = Some(size);
+            self.data = vec![0; size];
+            self.chunks = vec![false; size.div_ceil(DOWNLOAD_CHUNK_SIZE)];
+            self.requested = vec![None; self.chunks.len()];
+            self.state = DownloadState::Receiving;
+            return self.request_chunks(now);
+        }
+
+        let chunk = chunk as usize;
+        if self.state != DownloadState::Receiving || chunk >= self.chunks.len() {
+            return vec![];
+        }
+        if !self.chunks[chunk] {
+            let start = chunk * DOWNLOAD_CHUNK_SIZE;
+            let stop = (start + DOWNLOAD_CHUNK_SIZE).min(self.data.len());
+            let length = (stop - start).min(download.data.len());
+            self.data[start..start + length].copy_from_slice(&download.data[..length]);
+            self.chunks[chunk] = true;
+        }
+        self.request_chunks(now)
+    }
+
+    // requests the next chunk that was never requested and the ones that timed out
+    fn request_chunks(&mut self, now: Instant) -> Vec<String> {
+        let received = self.chunks.iter().filter(|c| **c).count();
+        self.percent = match self.chunks.len() {
+            0 => 100,
+            n => (received * 100 / n) as u8,
+        };
+        if received == self.chunks.len() {
+            self.state = DownloadState::Finished;
+            return vec![];
+        }
+        let mut commands = vec![];
+        let mut next_requested = false;
+        for (chunk, requested) in self.requested.iter_mut().enumerate() {
+            if self.chunks[chunk] {
+                continue;
+            }
+            let request = match requested {
+                Some(time) => now.saturating_duration_since(*time) >= CHUNK_TIMEOUT,
+                None if !next_requested => {
+                    next_requested = true;
+                    true
+                },
+                None => false,
+            };
+            if request {
+                *requested = Some(now);
+                commands.push(format!("nextdl {}", chunk));
+            }
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::network::connection::download::{FileDownload, DownloadState, CHUNK_TIMEOUT};
+    use crate::protocol::types::{Download, DOWNLOAD_CHUNK_SIZE};
+
+    #[test]
+    fn chunked_download() {
+        let mut download = FileDownload::new("maps/dm3.bsp");
+        let commands = download.handle(&Download{ chunk: Some(-1), size: DOWNLOAD_CHUNK_SIZE as i32 + 10, ..Default::default() });
+        assert_eq!(commands, vec!["nextdl 0"]);
+        let commands = download.handle(&Download{ chunk: Some(1), data: vec![2; DOWNLOAD_CHUNK_SIZE], ..Default::default() });
+        assert!(commands.is_empty());
+        let commands = download.handle(&Download{ chunk: Some(0), data: vec![1; DOWNLOAD_CHUNK_SIZE], ..Default::default() });
+        assert!(commands.is_empty());
+        assert_eq!(download.state, DownloadState::Finished);
+        assert_eq!(download.data.len(), DOWNLOAD_CHUNK_SIZE + 10);
+        assert_eq!(download.data[DOWNLOAD_CHUNK_SIZE - 1], 1);
+        assert_eq!(download.data[DOWNLOAD_CHUNK_SIZE], 2);
+    }
+
+    #[test]
+    fn out_of_order_chunks() {
+        let start = Instant::now();
+        let chunk = |chunk, value| Download{ chunk: Some(chunk), data: vec![value; DOWNLOAD_CHUNK_SIZE], ..Default::default() };
+        let mut download = FileDownload::new("maps/e1m2.bsp");
+        assert_eq!(download.handle_at(&Download{ chunk: Some(-1), size: 4 * DOWNLOAD_CHUNK_SIZE as i32, ..Default::default() }, start), vec!["nextdl 0"]);
+
+        // chunk 0 was only just requested, so only the next new chunk is asked for
+        let now = start + Duration::from_millis(100);
+        assert_eq!(download.handle_at(&chunk(2, 3), now), vec!["nextdl 1"]);
+        assert_eq!(download.handle_at(&chunk(1, 2), now), vec!["nextdl 3"]);
+        assert_eq!(download.percent, 50);
+        assert!(download.resend(now).is_empty());
+
+        // chunk 0 got lost
+        let now = start + CHUNK_TIMEOUT;
+        assert_eq!(download.handle_at(&chunk(3, 4), now), vec!["nextdl 0"]);
+        assert!(download.resend(now + Duration::from_millis(100)).is_empty());
+        assert_eq!(download.resend(now + CHUNK_TIMEOUT), vec!["nextdl 0"]);
+
+        assert!(download.handle_at(&chunk(0, 1), now + CHUNK_TIMEOUT).is_empty());
+        assert_eq!(download.state, DownloadState::Finished);
+        assert_eq!(download.data.iter().step_by(DOWNLOAD_CHUNK_SIZE).copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn block_download() {
+        let mut download = FileDownload::new("skins/base.pcx");
+        assert_eq!(download.handle(&Download{ size: 3, percent: 50, data: vec![1, 2, 3], ..Default::default() }), vec!["nextdl"]);
+        assert!(download.handle(&Download{ size: 2, percent: 100, data: vec![4, 5], ..Default::default() }).is_empty());
+        assert_eq!(download.state, DownloadState::Finished);
+        assert_eq!(download.data, vec![1, 2, 3, 4, 5]);
+
+        let mut download = FileDownload::new("maps/missing.bsp");
+        download.handle(&Download{ size: -1, ..Default::default() });
+        assert_eq!(download.state, DownloadState::Failed);
+    }
+}
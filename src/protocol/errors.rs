@@ -1,5 +1,7 @@
 use thiserror::Error;
 use crate::protocol::message::errors::MessageError;
+#[cfg(feature = "state")]
+use crate::state::errors::StateError;
 
 #[derive(Error, Debug)]
 pub enum MvdParseError {
@@ -11,6 +13,27 @@ pub enum MvdParseError {
     QwdCommand,
     #[error("read error {0}")]
     MessageError(MessageError),
+    #[error("incomplete frame, need {0} bytes but only {1} are available")]
+    Incomplete(usize, usize),
+    #[error("io error {0}")]
+    Io(std::io::Error),
+    #[error("cannot seek in a streamed demo")]
+    NotSeekable,
+    #[error("the index has no keyframe at or before frame {0}")]
+    NoKeyframe(u32),
+    #[error("cannot concat demos with a different {0} ({1} and {2})")]
+    ConcatMismatch(&'static str, String, String),
+    #[cfg(feature = "state")]
+    #[error("state error {0}")]
+    StateError(StateError),
+}
+
+impl MvdParseError {
+    /// the data ended in the middle of a frame, more data might still arrive
+    /// every other error means the data is corrupt
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, MvdParseError::Incomplete(..))
+    }
 }
 
 impl From<MessageError> for MvdParseError {
@@ -19,3 +42,23 @@ impl From<MessageError> for MvdParseError {
     }
 }
 
+#[cfg(feature = "state")]
+impl From<StateError> for MvdParseError {
+    fn from(err: StateError) -> MvdParseError {
+        MvdParseError::StateError(err)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum QwdParseError {
+    #[error("unhandled command ({0})")]
+    UnhandledCommand(u8),
+    #[error("read error {0}")]
+    MessageError(MessageError),
+}
+
+impl From<MessageError> for QwdParseError {
+    fn from(err: MessageError) -> QwdParseError {
+        QwdParseError::MessageError(err)
+    }
+}
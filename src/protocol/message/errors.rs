@@ -1,6 +1,6 @@
 use thiserror::Error;
 use serde::Serialize;
-use crate::protocol::message::ServerClient;
+use crate::protocol::message::{ServerClient, ClientServer};
 
 #[derive(Error, Debug, Serialize)]
 pub enum MessageError {
@@ -8,10 +8,14 @@ pub enum MessageError {
     ReadBeyondSize(usize, usize, usize),
     #[error("reading unhandled type: {0}")]
     UnhandledType(ServerClient),
+    #[error("reading unhandled client type: {0}")]
+    UnhandledClientType(ClientServer),
     #[error("reading unknown type: {0}")]
     UnknownType(u8),
     #[error("{0}")]
     StringError(String),
+    #[error("checksum({0}) does not match calculated checksum({1})")]
+    ChecksumMismatch(u8, u8),
     #[error("Bad read")]
     BadRead,
 }
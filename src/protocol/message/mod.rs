@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use crate::protocol::message::errors::MessageError;
 use paste::paste;
 use serde::Serialize;
@@ -49,19 +50,20 @@ impl MessageFlags {
     }
 }
 
+/// reads from a borrowed or owned buffer, writing always appends to an owned one
 #[derive(Serialize, Clone, Default, Debug)]
-pub struct Message
+pub struct Message<'a>
 {
     pub start: usize, // starting position in the buffer
     pub length: usize, // length of the message
     pub position: usize, // current read position after start
-    pub buffer: Box<Vec<u8>>,
+    pub buffer: Cow<'a, [u8]>,
     pub bigendian: bool,
     pub flags: MessageFlags,
     #[cfg(feature = "ascii_strings")]
     pub ascii_converter: AsciiConverter,
     #[cfg(feature = "trace")]
-    pub trace: MessageTrace,
+    pub trace: MessageTrace<'a>,
     pub r#type: MessageType
 }
 
@@ -113,7 +115,7 @@ macro_rules! endian_write{
                     } else {
                         a = v.to_le_bytes();
                     }
-                    self.buffer.extend_from_slice(&a);
+                    self.buffer.to_mut().extend_from_slice(&a);
                     self.position += TYPE_SIZE;
                     TYPE_SIZE
                 }
@@ -133,52 +135,115 @@ macro_rules! function {
     }}
 }
 
-impl From<Message> for Vec<u8> {
+impl From<Message<'_>> for Vec<u8> {
     fn from(m: Message) -> Vec<u8> {
-        *m.buffer
+        m.buffer.into_owned()
     }
 }
 
-impl From<&Message> for Vec<u8> {
+impl From<&Message<'_>> for Vec<u8> {
     fn from(m: &Message) -> Vec<u8> {
-        *m.buffer.clone()
+        m.buffer.to_vec()
     }
 }
 
-impl From<&mut Message> for Vec<u8> {
+impl From<&mut Message<'_>> for Vec<u8> {
     fn from(m: &mut Message) -> Vec<u8> {
-        *m.buffer.clone()
+        m.buffer.to_vec()
     }
 }
 
-impl Message {
+impl<'a> Message<'a> {
     endian_read!(u8, u16, u32, i8, i16, i32, f32);
     endian_write!(u8, u16, u32, i8, i16, i32, f32);
 
     pub fn read_bytes (&mut self, count: u32, readahead: bool) -> Result<Vec<u8>, MessageError> {
+        Ok(self.read_bytes_cow(count, readahead)?.into_owned())
+    }
+
+    /// same as [`Message::read_bytes`] but borrows from the buffer if the [`Message`] does
+    pub fn read_bytes_cow (&mut self, count: u32, readahead: bool) -> Result<Cow<'a, [u8]>, MessageError> {
         trace_start!(self, readahead);
-        let mut buf = Vec::new();
-        for i in 0..count {
-            buf.push(self.buffer[self.start + self.position + (i as usize) ]);
+        if let Err(e) = self.check_read_size(count as usize) {
+            trace_abort!(self);
+            return Err(e);
         }
+        let start = self.start + self.position;
+        let buf = self.slice(start, start + count as usize);
         if !readahead {
             self.position += count as usize;
         }
 
-        trace_stop!(self, buf.clone());
+        trace_stop!(self, buf.to_vec());
         Ok(buf)
     }
 
-    pub fn write_stringbyte (&mut self, string: String ) ->  usize {
+    fn slice(&self, start: usize, stop: usize) -> Cow<'a, [u8]> {
+        match &self.buffer {
+            Cow::Borrowed(buffer) => {
+                let buffer: &'a [u8] = buffer;
+                Cow::Borrowed(&buffer[start..stop])
+            },
+            Cow::Owned(buffer) => Cow::Owned(buffer[start..stop].to_vec()),
+        }
+    }
+
+    pub fn write_bytes (&mut self, bytes: &[u8]) ->  usize {
+        self.buffer.to_mut().extend_from_slice(bytes);
+        self.position += bytes.len();
+        bytes.len()
+    }
+
+    pub fn write_stringbyte (&mut self, string: &StringByte) ->  usize {
+        let size = self.write_bytes(&string.bytes);
+        size + self.write_u8(0)
+    }
+
+    pub fn write_stringvector (&mut self, strings: &StringVector) ->  usize {
         let mut size: usize = 0;
-        let s = string.as_bytes();
-        for c in s {
-            self.buffer.push(*c);
-            size += 1;
+        for string in strings {
+            size += self.write_stringbyte(string);
         }
-        self.buffer.push(0_u8);
-        size += 1;
-        size
+        size + self.write_u8(0)
+    }
+
+    pub fn write_coordinate(&mut self, coordinate: Coordinate) -> usize {
+        if self.flags.fte_protocol_extensions.contains(FteProtocolExtensions::FLOATCOORDS) {
+            return self.write_f32(coordinate);
+        }
+        self.write_i16((coordinate * 8.0).round() as i16)
+    }
+
+    pub fn write_coordinatevector(&mut self, coordinates: &CoordinateVector) -> usize {
+        self.write_coordinate(coordinates.x) +
+            self.write_coordinate(coordinates.y) +
+            self.write_coordinate(coordinates.z)
+    }
+
+    pub fn write_entity_coordinate(&mut self, coordinate: Coordinate) -> usize {
+        if self.flags.mvd_protocol_extension.contains(MvdProtocolExtensions::FLOATCOORDS) {
+            return self.write_f32(coordinate);
+        }
+        self.write_coordinate(coordinate)
+    }
+
+    pub fn write_entity_coordinatevector(&mut self, coordinates: &CoordinateVector) -> usize {
+        self.write_entity_coordinate(coordinates.x) +
+            self.write_entity_coordinate(coordinates.y) +
+            self.write_entity_coordinate(coordinates.z)
+    }
+
+    pub fn write_angle(&mut self, angle: Angle) -> usize {
+        if self.flags.fte_protocol_extensions.contains(FteProtocolExtensions::FLOATCOORDS) {
+            return self.write_angle16(angle);
+        }
+        self.write_u8((angle * 256.0 / 360.0).round() as i32 as u8)
+    }
+
+    pub fn write_anglevector(&mut self, angles: &AngleVector) -> usize {
+        self.write_angle(angles.x) +
+            self.write_angle(angles.y) +
+            self.write_angle(angles.z)
     }
 
     pub fn write_client_command_string (&mut self, string: impl Into<String>) ->  usize {
@@ -199,13 +264,13 @@ impl Message {
         let mut s: usize = 1;
         self.write_u8(ClientServer::StringCommand as u8);
         s += string.len();
-        self.buffer.extend(string);
-        self.buffer.push(0_u8);
+        self.buffer.to_mut().extend(string);
+        self.buffer.to_mut().push(0_u8);
         s
     }
 
     pub fn write_angle16(&mut self, angle: f32) -> usize {
-        self.write_u16((angle *65536.0 / 360.0) as u16)
+        self.write_u16((angle * 65536.0 / 360.0).round() as i32 as u16)
     }
 
     pub fn write_delta_usercommand(&mut self, delta_usercommand: DeltaUserCommand) ->  usize {
@@ -254,32 +319,37 @@ impl Message {
         if delta_usercommand.msec.is_some() {
             s += self.write_u8(delta_usercommand.msec.unwrap());
         }
-        self.buffer[position] = bits.bits();
+        self.buffer.to_mut()[position] = bits.bits();
         s
     }
 
-#[cfg(not(feature = "ascii_strings"))]
-    pub fn read_stringbyte (&mut self, readahead: bool) ->  Result<StringByte, MessageError> {
+    /// reads a null terminated string, borrows from the buffer if the [`Message`] does
+    pub fn read_stringbyte_cow (&mut self, readahead: bool) ->  Result<Cow<'a, [u8]>, MessageError> {
         trace_start!(self, readahead);
-        let mut buf =  Vec::new();
-        let original_position = self.position;
-        buf.clear();
-        loop {
-            let b = self.read_u8(false)?;
-            if b == 255 {
-                continue;
-            } else if b == 0 {
-                break
+        let start = self.start + self.position;
+        let end = (self.start + self.length).min(self.buffer.len());
+        let stop = match self.buffer[start.min(end)..end].iter().position(|b| *b == 0) {
+            Some(p) => start + p,
+            None => {
+                trace_abort!(self);
+                return Err(MessageError::ReadBeyondSize(self.length, self.length, 1));
             }
-
-            buf.push(b);
+        };
+        let mut bytes = self.slice(start, stop);
+        if bytes.contains(&255) {
+            bytes = Cow::Owned(bytes.iter().filter(|b| **b != 255).copied().collect());
         }
-
-        if readahead {
-            self.position = original_position;
+        if !readahead {
+            self.position += stop - start + 1;
         }
-        trace_stop!(self, buf.clone());
-        Ok(StringByte{ bytes: buf})
+        trace_stop!(self, bytes.to_vec());
+        Ok(bytes)
+    }
+
+#[cfg(not(feature = "ascii_strings"))]
+    pub fn read_stringbyte (&mut self, readahead: bool) ->  Result<StringByte<'a>, MessageError> {
+        let bytes = self.read_stringbyte_cow(readahead)?;
+        Ok(StringByte{ bytes })
     }
 
 
@@ -290,30 +360,22 @@ impl Message {
     }
 
 #[cfg(feature = "ascii_strings")]
-    pub fn read_stringbyte (&mut self, readahead: bool) ->  Result<StringByte, MessageError> {
+    pub fn read_stringbyte (&mut self, readahead: bool) ->  Result<StringByte<'a>, MessageError> {
         trace_start!(self, readahead);
-        let mut buf : Vec<u8> =  Vec::new();
-        let original_position = self.position;
-        buf.clear();
         trace_lock!(self);
-        loop {
-            let b = self.read_u8(false)?;
-            if b == 255 {
-                continue;
-            } else if b == 0 {
-                break
-            }
-            buf.push(b);
-        }
+        let bytes = self.read_stringbyte_cow(readahead);
         trace_unlock!(self);
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                trace_abort!(self);
+                return Err(e);
+            },
+        };
 
-        if readahead {
-            self.position = original_position;
-        }
-
-        let string = self.to_ascii(buf.clone());
+        let string = self.to_ascii(bytes.as_ref());
         let v = StringByte{
-            bytes: buf,
+            bytes,
             string,
         };
 
@@ -321,7 +383,7 @@ impl Message {
         Ok(v)
     }
 
-    pub fn read_stringvector (&mut self, readahead: bool) ->  Result<Vec<StringByte>, MessageError> {
+    pub fn read_stringvector (&mut self, readahead: bool) ->  Result<StringVector<'a>, MessageError> {
         trace_start!(self, readahead);
         let mut strings =  Vec::new();
         strings.clear();
@@ -350,6 +412,28 @@ impl Message {
         Ok(s * (1.0/8.0))
     }
 
+    /// entity and player origins, these are also floats with [`MvdProtocolExtensions::FLOATCOORDS`]
+    pub fn read_entity_coordinate(&mut self, readahead: bool) ->  Result<Coordinate, MessageError> {
+        trace_start!(self, readahead);
+        if self.flags.mvd_protocol_extension.contains(MvdProtocolExtensions::FLOATCOORDS) {
+            let f = self.read_f32(readahead)?;
+            trace_stop!(self);
+            return Ok(f);
+        }
+        let v = self.read_coordinate(readahead)?;
+        trace_stop!(self);
+        Ok(v)
+    }
+
+    pub fn read_entity_coordinatevector(&mut self, readahead: bool) ->  Result<CoordinateVector, MessageError> {
+        trace_start!(self, readahead);
+        let x = self.read_entity_coordinate(readahead)?;
+        let y = self.read_entity_coordinate(readahead)?;
+        let z = self.read_entity_coordinate(readahead)?;
+        trace_stop!(self);
+        Ok(CoordinateVector{x, y, z})
+    }
+
     pub fn read_coordinatevector(&mut self, readahead: bool) ->  Result<CoordinateVector, MessageError> {
         trace_start!(self, readahead);
         let x = self.read_coordinate(readahead)?;
@@ -374,7 +458,7 @@ impl Message {
     pub fn read_angle16(&mut self, readahead: bool) ->  Result<Angle, MessageError> {
         trace_start!(self, readahead);
         let s = self.read_u16(readahead)? as f32;
-        let v = s * (360.0/65535.0);
+        let v = s * (360.0/65536.0);
         trace_stop!(self, v);
         Ok(v)
     }
@@ -388,12 +472,12 @@ impl Message {
         Ok(AngleVector{x, y, z})
     }
 
-    pub fn empty() -> Message {
+    pub fn empty() -> Message<'a> {
         Message {
             start: 0,
             length: 0,
             position: 0,
-            buffer: Box::<Vec<u8>>::default(),
+            buffer: Cow::Owned(vec![]),
             bigendian: false,
             flags: MessageFlags::new_empty(),
             #[cfg(feature = "ascii_strings")]
@@ -403,7 +487,7 @@ impl Message {
         }
     }
 
-    pub fn empty_be() -> Message {
+    pub fn empty_be() -> Message<'a> {
         Message {
             bigendian: true,
             r#type: MessageType::Connection,
@@ -412,7 +496,7 @@ impl Message {
     }
 
     #[cfg(feature = "ascii_strings")]
-    pub fn new (buffer: Box<Vec<u8>>, start: usize, length: usize, bigendian: bool, flags: MessageFlags, maybe_ascii_converter: Option<AsciiConverter>, r#type: MessageType) -> Message {
+    pub fn new (buffer: impl Into<Cow<'a, [u8]>>, start: usize, length: usize, bigendian: bool, flags: MessageFlags, maybe_ascii_converter: Option<AsciiConverter>, r#type: MessageType) -> Message<'a> {
         let ascii_converter: AsciiConverter;
         if let Some(ascii_convter_in) = maybe_ascii_converter {
             ascii_converter = ascii_convter_in;
@@ -424,7 +508,7 @@ impl Message {
             start,
             length,
             position: 0,
-            buffer,
+            buffer: buffer.into(),
             bigendian,
             flags,
             ascii_converter,
@@ -434,13 +518,13 @@ impl Message {
     }
 
     #[cfg(not(feature = "ascii_strings"))]
-    pub fn new (buffer: Box<Vec<u8>>, start: usize, length: usize, bigendian: bool, flags: MessageFlags, r#type: MessageType) -> Message {
+    pub fn new (buffer: impl Into<Cow<'a, [u8]>>, start: usize, length: usize, bigendian: bool, flags: MessageFlags, r#type: MessageType) -> Message<'a> {
 
         Message {
             start,
             length,
             position: 0,
-            buffer,
+            buffer: buffer.into(),
             bigendian,
             flags,
             r#type,
@@ -449,11 +533,7 @@ impl Message {
     }
 
     pub fn get_range(&mut self, start: usize, length: usize) -> Vec<u8> {
-        let mut buf = Vec::new();
-        for i in self.start + start .. self.start + start + length {
-            buf.push(self.buffer[i]);
-        }
-        buf
+        self.buffer[self.start + start .. self.start + start + length].to_vec()
     }
 
     #[inline]
@@ -479,7 +559,7 @@ impl Message {
          Ok(false)
     }
 
-    pub fn read_packet(&mut self) -> Result<Packet, MessageError> {
+    pub fn read_packet(&mut self) -> Result<Packet<'a>, MessageError> {
         let is_oob = self.is_oob()?;
         if is_oob {
             return self.read_oob_packet();
@@ -487,7 +567,7 @@ impl Message {
         self.read_connected_packet()
     }
 
-    pub fn read_connected_packet(&mut self) -> Result<Packet, MessageError> {
+    pub fn read_connected_packet(&mut self) -> Result<Packet<'a>, MessageError> {
         trace_start!(self, false);
         trace_annotate!(self, "sequence");
         let sequence = self.read_u32(false)?;
@@ -555,7 +635,56 @@ impl Message {
         Ok(p)
     }
 
-    pub fn read_oob_packet(&mut self) -> Result<Packet, MessageError> {
+    /// reads a packet sent from the client to the server
+    pub fn read_client_packet(&mut self) -> Result<ClientPacket<'a>, MessageError> {
+        if self.is_oob()? {
+            trace_start!(self, false);
+            trace_annotate!(self, "header");
+            let _ = self.read_i32(false)?;
+            trace_annotate!(self, "command");
+            let command = self.read_bytes((self.length - self.position) as u32, false)?;
+            let p = ClientPacket::ConnectionLess(command);
+            trace_stop!(self, p);
+            return Ok(p);
+        }
+
+        trace_start!(self, false);
+        trace_annotate!(self, "sequence");
+        let sequence = self.read_u32(false)?;
+        trace_annotate!(self, "sequence_ack");
+        let sequence_ack = self.read_u32(false)?;
+        trace_annotate!(self, "qport");
+        let qport = self.read_u16(false)?;
+        let mut packet = ClientConnected{
+            sequence,
+            sequence_ack,
+            qport,
+            messages: Vec::new(),
+        };
+
+        while self.position < self.length {
+            trace_annotate!(self, "message type");
+            let t = self.read_u8(false)?;
+            let ret = match ClientServer::try_from(t) {
+                Ok(cmd) => cmd.read_message(self, sequence),
+                Err(_) => Err(MessageError::UnknownType(t)),
+            };
+            match ret {
+                Ok(ret) => packet.messages.push(ret),
+                Err(e) => {
+                    let p = ClientPacket::Connected(packet);
+                    trace_stop!(self, p);
+                    return Err(e);
+                }
+            }
+        }
+
+        let p = ClientPacket::Connected(packet);
+        trace_stop!(self, p);
+        Ok(p)
+    }
+
+    pub fn read_oob_packet(&mut self) -> Result<Packet<'a>, MessageError> {
         trace_start!(self, false);
         trace_annotate!(self, "header");
         let _ = self.read_i32(false)?;
@@ -573,7 +702,7 @@ impl Message {
         }
     }
 
-    fn read_packet_s2c_challenge(&mut self) -> Result<Packet, MessageError> {
+    fn read_packet_s2c_challenge(&mut self) -> Result<Packet<'a>, MessageError> {
         let mut flags = MessageFlags::new_empty();
         trace_start!(self, false);
         trace_annotate!(self, "command string");
@@ -625,9 +754,7 @@ impl Message {
 
     pub fn replace_at_position(&mut self, bytes: impl Into<Vec<u8>>, position: usize) -> Result<(), MessageError> {
         let bytes = bytes.into();
-        for (i, c) in bytes.iter().enumerate() {
-            self.buffer[position+i] = *c;
-        }
+        self.buffer.to_mut()[position..position + bytes.len()].copy_from_slice(&bytes);
         Ok(())
     }
 }
@@ -8,15 +8,15 @@ use crate::mvd::*;
 
 #[cfg(feature = "trace")]
 #[derive(Serialize, Clone, Debug, Default)]
-pub struct ReadTrace {
+pub struct ReadTrace<'a> {
     pub start: usize,
     pub stop: usize,
     pub readahead: bool,
     pub aborted: bool,
     pub function: String,
     pub annotation: Option<String>,
-    pub read: Vec<ReadTrace>,
-    pub value: TraceValue,
+    pub read: Vec<ReadTrace<'a>>,
+    pub value: TraceValue<'a>,
 }
 
 /*
@@ -42,15 +42,15 @@ pub enum TraceValue {
 
 #[cfg(feature = "trace")]
 #[derive(Serialize, Clone, Default, Debug)]
-pub struct MessageTrace {
+pub struct MessageTrace<'a> {
     pub annotation: Option<String>,
-    pub stack: Vec<ReadTrace>,
-    pub read: Vec<ReadTrace>,
+    pub stack: Vec<ReadTrace<'a>>,
+    pub read: Vec<ReadTrace<'a>>,
     pub enabled: bool,
     pub locked: bool,
 }
 
-impl MessageTrace {
+impl MessageTrace<'_> {
     pub fn clear(&mut self) {
         self.stack.clear();
         self.read.clear();
@@ -58,12 +58,12 @@ impl MessageTrace {
     }
 }
 
-pub(crate) trait ToTraceValue {
-    fn to_tracevalue(&self) -> TraceValue;
+pub(crate) trait ToTraceValue<'a> {
+    fn to_tracevalue(&self) -> TraceValue<'a>;
 }
 
 
-impl Message {
+impl<'a> Message<'a> {
     #[cfg(feature = "trace")]
     pub fn read_trace_annotate(&mut self, annotation: &str) {
         if !self.trace.enabled {
@@ -115,7 +115,7 @@ impl Message {
     }
 
     #[cfg(feature = "trace")]
-    pub fn read_trace_stop(&mut self, value: TraceValue) {
+    pub fn read_trace_stop(&mut self, value: TraceValue<'a>) {
         if !self.trace.enabled {
             return;
         }
@@ -230,20 +230,20 @@ macro_rules! trace_unlock {
 pub(crate) use trace_unlock;
 
 macro_rules! create_trace_enums{
-    ($(($ty:ident, $en:ident)), *) => {
+    ($(($ty:ident $(<$lt:lifetime>)?, $en:ident)), *) => {
         paste! {
             #[derive(Debug, Default, PartialEq, PartialOrd, Display, Serialize, Clone)]
-            pub enum TraceValue{
+            pub enum TraceValue<'a>{
                 #[default] None,
                 VecU8(Vec<u8>),
                 $(
-                [< $en >]([< $ty >]),
+                [< $en >]([< $ty >]$(<$lt>)?),
                 )*
             }
 
             $(
-                impl ToTraceValue for $ty {
-                    fn to_tracevalue(&self) -> TraceValue {
+                impl<'a> ToTraceValue<'a> for $ty$(<$lt>)? {
+                    fn to_tracevalue(&self) -> TraceValue<'a> {
                         TraceValue::[< $en >](self.clone())
                     }
                 }
@@ -265,8 +265,8 @@ macro_rules! function {
 pub(crate) use function;
 
 
-impl ToTraceValue for Vec<u8> {
-    fn to_tracevalue(&self) -> TraceValue {
+impl<'a> ToTraceValue<'a> for Vec<u8> {
+    fn to_tracevalue(&self) -> TraceValue<'a> {
         TraceValue::VecU8(self.clone())
     }
 }
@@ -280,10 +280,12 @@ create_trace_enums!(
     (i16, I16),
     (i32, I32),
     (f32, F32),
-    (ServerMessage, ServerMessage),
-    (Packet, Packet),
-    (StringByte, StringByte),
+    (ServerMessage<'a>, ServerMessage),
+    (Packet<'a>, Packet),
+    (ClientMessage<'a>, ClientMessage),
+    (ClientPacket<'a>, ClientPacket),
+    (StringByte<'a>, StringByte),
     (DeltaUserCommand, DeltaUserCommand),
-    (StringVector, StringVector),
-    (MvdFrame, MvdFrame));
+    (StringVector<'a>, StringVector),
+    (MvdFrame<'a>, MvdFrame));
 
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::borrow::Cow;
 use num_enum::TryFromPrimitive;
 use strum_macros::Display;
 use paste::paste;
@@ -10,6 +11,7 @@ use crate::protocol::message::errors::*;
 use crate::protocol::message::trace::*;
 
 use protocol_macros::ParseMessage;
+use crate::crc::generate_checksum;
 
 #[cfg(feature = "ascii_strings")]
 use crate::utils::ascii_converter::AsciiConverter;
@@ -65,15 +67,15 @@ impl DeltaUserCommand {
             }
             if flags.contains(UserCommandFlags::FORWARD) {
                 trace_annotate!(message, "forward");
-                forward = Some((message.read_i8(false)? << 3) as i16);
+                forward = Some((message.read_i8(false)? as i16) << 3);
             }
             if flags.contains(UserCommandFlags::SIDE) {
                 trace_annotate!(message, "side");
-                side = Some((message.read_i8(false)? << 3) as i16);
+                side = Some((message.read_i8(false)? as i16) << 3);
             }
             if flags.contains(UserCommandFlags::UP) {
                 trace_annotate!(message, "up");
-                up = Some((message.read_i8(false)? << 3) as i16);
+                up = Some((message.read_i8(false)? as i16) << 3);
             }
         } else {
             if flags.contains(UserCommandFlags::ANGLE1) {
@@ -139,20 +141,67 @@ impl DeltaUserCommand {
         trace_stop!(message, r);
         Ok(r)
     }
+
+    pub fn write(&self, message: &mut Message) -> usize {
+        if message.flags.protocol > 26 {
+            return message.write_delta_usercommand(self.clone());
+        }
+        let mut size: usize = 0;
+        let mut bits = UserCommandFlags::empty();
+        let position = message.position;
+        size += message.write_u8(0);
+        if let Some(angle) = self.angle.x {
+            bits |= UserCommandFlags::ANGLE1;
+            size += message.write_angle16(angle);
+        }
+        size += message.write_angle16(self.angle.y.unwrap_or_default());
+        if let Some(angle) = self.angle.z {
+            bits |= UserCommandFlags::ANGLE3;
+            size += message.write_angle16(angle);
+        }
+        if let Some(forward) = self.forward {
+            bits |= UserCommandFlags::FORWARD;
+            size += message.write_i8((forward >> 3) as i8);
+        }
+        if let Some(side) = self.side {
+            bits |= UserCommandFlags::SIDE;
+            size += message.write_i8((side >> 3) as i8);
+        }
+        if let Some(up) = self.up {
+            bits |= UserCommandFlags::UP;
+            size += message.write_i8((up >> 3) as i8);
+        }
+        if let Some(buttons) = self.buttons {
+            bits |= UserCommandFlags::BUTTONS;
+            size += message.write_u8(buttons);
+        }
+        if let Some(impulse) = self.impulse {
+            bits |= UserCommandFlags::IMPULSE;
+            size += message.write_u8(impulse);
+        }
+        // ANGLE2 here is CM_MSEC in the original
+        if let Some(msec) = self.msec {
+            bits |= UserCommandFlags::ANGLE2;
+            size += message.write_u8(msec);
+        }
+        message.buffer.to_mut()[message.start + position] = bits.bits();
+        size
+    }
 }
 
+/// a string as sent by the server, `bytes` borrow from the message buffer when possible
 #[derive(PartialOrd, PartialEq, Eq, Clone, Debug, Serialize, Default)]
-pub struct StringByte {
-    pub bytes: Vec<u8>,
+pub struct StringByte<'a> {
+    pub bytes: Cow<'a, [u8]>,
     #[cfg(feature = "ascii_strings")]
     pub string: String,
 }
 #[cfg(feature = "ascii_strings")]
-impl fmt::Display for StringByte{
+impl fmt::Display for StringByte<'_>{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "String: {},", self.string)?;
         write!(f, "Byte:")?;
-        for x in &self.bytes {
+        for x in self.bytes.iter() {
             write!(f, " {:0>2x}", x)?;
         }
         Ok(())
@@ -160,10 +209,10 @@ impl fmt::Display for StringByte{
 }
 
 #[cfg(not(feature = "ascii_strings"))]
-impl fmt::Display for StringByte{
+impl fmt::Display for StringByte<'_>{
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Byte:")?;
-        for x in &self.bytes {
+        for x in self.bytes.iter() {
             write!(f, " {:0>2x}", x)?;
         }
         Ok(())
@@ -171,27 +220,36 @@ impl fmt::Display for StringByte{
 }
 
 
-impl StringByte {
+impl StringByte<'_> {
 #[cfg(not(feature = "ascii_strings"))]
-    pub fn new (bytes: impl Into<Vec<u8>>) -> StringByte {
+    pub fn new (bytes: impl Into<Vec<u8>>) -> StringByte<'static> {
         let bytes = bytes.into();
         StringByte{
-            bytes,
+            bytes: Cow::Owned(bytes),
         }
     }
 
 #[cfg(feature = "ascii_strings")]
-    pub fn new (bytes: impl Into<Vec<u8>>, ascii_converter: &AsciiConverter) -> StringByte {
+    pub fn new (bytes: impl Into<Vec<u8>>, ascii_converter: &AsciiConverter) -> StringByte<'static> {
         let bytes = bytes.into();
         let string = ascii_converter.convert(bytes.clone());
         StringByte{
-            bytes,
+            bytes: Cow::Owned(bytes),
             string,
         }
     }
+
+    /// copies the bytes if they are borrowed
+    pub fn into_owned(self) -> StringByte<'static> {
+        StringByte{
+            bytes: Cow::Owned(self.bytes.into_owned()),
+            #[cfg(feature = "ascii_strings")]
+            string: self.string,
+        }
+    }
 }
 
-pub type StringVector = Vec<StringByte>;
+pub type StringVector<'a> = Vec<StringByte<'a>>;
 
 
 pub type Coordinate = f32;
@@ -202,7 +260,28 @@ pub struct CoordinateVector {
     pub z: Coordinate,
 }
 
+impl CoordinateVector {
+    pub fn lerp(&self, to: &CoordinateVector, t: f32) -> CoordinateVector {
+        CoordinateVector {
+            x: self.x + (to.x - self.x) * t,
+            y: self.y + (to.y - self.y) * t,
+            z: self.z + (to.z - self.z) * t,
+        }
+    }
+
+    pub fn distance(&self, to: &CoordinateVector) -> f32 {
+        ((to.x - self.x).powi(2) + (to.y - self.y).powi(2) + (to.z - self.z).powi(2)).sqrt()
+    }
+}
+
 pub type Velocity = i16;
+#[derive(Debug, PartialEq, Eq, PartialOrd, Copy, Clone, Serialize, Default)]
+pub struct VelocityVector {
+    pub x: Velocity,
+    pub y: Velocity,
+    pub z: Velocity,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Copy, Clone, Serialize, Default)]
 pub struct VelocityVectorOption { 
     pub x: Option<Velocity>,
@@ -254,6 +333,22 @@ pub struct AngleVector {
     pub z: Angle,
 }
 
+/// interpolates a single angle in degrees, always takes the shorter way around
+fn lerp_angle(from: Angle, to: Angle, t: f32) -> Angle {
+    let delta = (to - from + 180.0).rem_euclid(360.0) - 180.0;
+    from + delta * t
+}
+
+impl AngleVector {
+    /// the result isn't normalized, 350 to 10 passes 360 instead of going back through 180
+    pub fn lerp(&self, to: &AngleVector, t: f32) -> AngleVector {
+        AngleVector {
+            x: lerp_angle(self.x, to.x, t),
+            y: lerp_angle(self.y, to.y, t),
+            z: lerp_angle(self.z, to.z, t),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, PartialOrd, Copy, Clone, Serialize, Default)]
 pub struct AngleVectorOption { 
@@ -366,21 +461,21 @@ bitflags! {
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Clone, Serialize, Default)]
-pub struct Serverdata {
+pub struct Serverdata<'a> {
     pub protocol: ProtocolVersion,
     pub fte_protocol_extension: FteProtocolExtensions,
     pub fte_protocol_extension_2: FteProtocolExtensions2,
     pub mvd_protocol_extension: MvdProtocolExtensions,
     pub servercount: u32,
     pub demotime: f32,
-    pub gamedir: StringByte,
+    pub gamedir: StringByte<'a>,
     pub player_number: u8,
-    pub map: StringByte,
+    pub map: StringByte<'a>,
     pub movevars: [f32;10]
 }
 
-impl Serverdata {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+impl Serverdata<'_> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         let mut protocol: ProtocolVersion;
         let mut fte_protocol_extension = FteProtocolExtensions::empty();
         let mut fte_protocol_extension_2 = FteProtocolExtensions2::empty();
@@ -460,19 +555,62 @@ impl Serverdata {
         trace_stop!(message, r);
         Ok(r)
     }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        let mut size: usize = 0;
+        if !self.fte_protocol_extension.is_empty() {
+            size += message.write_u32(ProtocolVersion::Fte as u32);
+            size += message.write_u32(self.fte_protocol_extension.bits());
+        }
+        if !self.fte_protocol_extension_2.is_empty() {
+            size += message.write_u32(ProtocolVersion::Fte2 as u32);
+            size += message.write_u32(self.fte_protocol_extension_2.bits());
+        }
+        if !self.mvd_protocol_extension.is_empty() {
+            size += message.write_u32(ProtocolVersion::Mvd1 as u32);
+            size += message.write_u32(self.mvd_protocol_extension.bits());
+        }
+        size += message.write_u32(ProtocolVersion::Standard as u32);
+        size += message.write_u32(self.servercount);
+        size += message.write_stringbyte(&self.gamedir);
+        match message.r#type {
+            MessageType::Connection => {
+                size += message.write_u8(self.player_number);
+            },
+            MessageType::Mvd => {
+                size += message.write_f32(self.demotime);
+            },
+            _ => {
+                return Err(MessageError::StringError("MessageType None should never happen, probable writer error".to_string()))
+            }
+        }
+        size += message.write_stringbyte(&self.map);
+        for mv in self.movevars {
+            size += message.write_f32(mv);
+        }
+        Ok(size)
+    }
+
+    pub fn into_owned(self) -> Serverdata<'static> {
+        Serverdata{
+            gamedir: self.gamedir.into_owned(),
+            map: self.map.into_owned(),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Soundlist {
+pub struct Soundlist<'a> {
     pub start: u8,
-    pub sounds: StringVector,
+    pub sounds: StringVector<'a>,
     pub offset: u8
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Modellist {
+pub struct Modellist<'a> {
     pub start: u8,
-    pub models: StringVector,
+    pub models: StringVector<'a>,
     pub offset: u8
 }
 
@@ -482,8 +620,8 @@ pub struct Cdtrack {
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Stufftext {
-    pub text: StringByte 
+pub struct Stufftext<'a> {
+    pub text: StringByte<'a> 
 }
 
 #[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Default, Copy, Clone)]
@@ -540,10 +678,10 @@ pub struct Updateentertime {
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Updateuserinfo {
+pub struct Updateuserinfo<'a> {
     pub player_number: u8,
     pub uid: u32,
-    pub userinfo: StringByte
+    pub userinfo: StringByte<'a>
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
@@ -559,20 +697,20 @@ pub struct Updatestat{
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Lightstyle {
+pub struct Lightstyle<'a> {
     pub index: u8,
-    pub style: StringByte 
+    pub style: StringByte<'a> 
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Serverinfo {
-    pub key: StringByte,
-    pub value: StringByte 
+pub struct Serverinfo<'a> {
+    pub key: StringByte<'a>,
+    pub value: StringByte<'a> 
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Centerprint {
-    pub message: StringByte 
+pub struct Centerprint<'a> {
+    pub message: StringByte<'a> 
 }
 
 bitflags! {
@@ -619,16 +757,33 @@ pub enum Playerinfo {
     PlayerinfoConnectionT(PlayerinfoConnection)
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+impl Playerinfo {
+    pub fn player_number(&self) -> u8 {
+        match self {
+            Playerinfo::PlayerinfoMvdT(playerinfo) => playerinfo.player_number,
+            Playerinfo::PlayerinfoConnectionT(playerinfo) => playerinfo.player_number,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
+#[qw(nested)]
 pub struct PlayerinfoMvd {
     pub player_number: u8,
+    #[qw(bits = "u16")]
     pub flags: DfTypes,
     pub frame: u8,
+    #[qw(entity_coordinate, coord_from_flags = "flags.contains(DfTypes::ORIGIN), flags.contains(DfTypes::ORIGIN2), flags.contains(DfTypes::ORIGIN3)")]
     pub origin: Option<CoordinateVectorOption>,
+    #[qw(angle16, angle_from_flags = "flags.contains(DfTypes::ANGLE), flags.contains(DfTypes::ANGLE2), flags.contains(DfTypes::ANGLE3)")]
     pub angle: Option<AngleVectorOption>,
+    #[qw(if = "flags.contains(DfTypes::MODEL)")]
     pub model: Option<u8>,
+    #[qw(if = "flags.contains(DfTypes::SKINNUM)")]
     pub skinnum: Option<u8>,
+    #[qw(if = "flags.contains(DfTypes::EFFECTS)")]
     pub effects: Option<u8>,
+    #[qw(if = "flags.contains(DfTypes::WEAPONFRAME)")]
     pub weaponframe: Option<u8>,
 }
 
@@ -648,99 +803,15 @@ pub struct PlayerinfoConnection{
     pub alpha: Option<u8>
 }
 
-fn playerinfo_read_demo(message: &mut Message) -> Result<ServerMessage, MessageError> {
-    let player_number = message.read_u8(false)?;
-    let u = message.read_u16(false)?;
-    let flags = DfTypes::from_bits_truncate(u);
-    let frame = message.read_u8(false)?;
-    let mut origin = None;
-    let mut origin_read = false;
-    let mut origin_vec =  CoordinateVectorOption{x: None, y: None, z: None};
-    for i in 0..3 {
-        let f = DfTypes::from_bits_truncate(DfTypes::ORIGIN.bits() << i);
-        if flags.contains(f) {
-            if !origin_read {
-                origin_read = true;
-            }
-            let f = message.read_coordinate(false)?;
-            match i {
-                0 => origin_vec.x = Some(f),
-                1 => origin_vec.y = Some(f),
-                2 => origin_vec.z = Some(f),
-                _ => return Err(MessageError::StringError("this is weird".to_string())),
-            }
-        }
-    }
-    if origin_read {
-        origin = Some(origin_vec);
-    }
-    let mut angle = None;
-    let mut angle_read = false;
-    let mut angle_vec =  AngleVectorOption{x: None, y: None, z: None};
-    for i in 0..3 {
-        let f = DfTypes::from_bits_truncate(DfTypes::ANGLE.bits() << i);
-        if flags.contains(f) {
-            if !angle_read {
-                angle_read = true;
-            }
-            let f = message.read_angle16(false)?;
-            match i {
-                0 => angle_vec.x = Some(f),
-                1 => angle_vec.y = Some(f),
-                2 => angle_vec.z = Some(f),
-                _ => return Err(MessageError::StringError("this is weird".to_string())),
-            }
-        }
-    }
-    if angle_read {
-        angle = Some(angle_vec);
-    }
-
-    let mut model = None;
-    if flags.contains(DfTypes::MODEL) {
-        let i = message.read_u8(false)?;
-        model = Some(i);
-    }
-
-    let mut skinnum = None;
-    if flags.contains(DfTypes::SKINNUM) {
-        let i = message.read_u8(false)?;
-        skinnum = Some(i);
-    }
-
-    let mut effects = None;
-    if flags.contains(DfTypes::EFFECTS) {
-        let i = message.read_u8(false)?;
-        effects = Some(i);
-    }
-
-    let mut weaponframe = None;
-    if flags.contains(DfTypes::WEAPONFRAME) {
-        let i = message.read_u8(false)?;
-        weaponframe = Some(i);
-    }
-    Ok(ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
-        player_number,
-        flags,
-        frame,
-        origin,
-        angle,
-        model,
-        skinnum,
-        effects,
-        weaponframe
-    })))
-}
-
-fn playerinfo_read_connection(message: &mut Message) -> Result<ServerMessage, MessageError> {
+fn playerinfo_read_connection<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
     let player_number = message.read_u8(false)?;
     let u = message.read_u16(false)?;
     let flags = PFTypes::from_bits_truncate(u as u32);
     let mut origin = CoordinateVector{..Default::default()};
 
-    origin.x = message.read_coordinate(false)?;
-    origin.y = message.read_coordinate(false)?;
-    origin.z = message.read_coordinate(false)?;
+    origin.x = message.read_entity_coordinate(false)?;
+    origin.y = message.read_entity_coordinate(false)?;
+    origin.z = message.read_entity_coordinate(false)?;
 
     let frame = message.read_u8(false)?;
 
@@ -807,12 +878,58 @@ fn playerinfo_read_connection(message: &mut Message) -> Result<ServerMessage, Me
     })))
 }
 
+fn playerinfo_write_connection(message: &mut Message, playerinfo: &PlayerinfoConnection) -> Result<usize, MessageError> {
+    let mut size: usize = 0;
+    size += message.write_u8(playerinfo.player_number);
+    size += message.write_u16((playerinfo.flags.bits() & 0xffff) as u16);
+    size += message.write_entity_coordinatevector(&playerinfo.origin);
+    size += message.write_u8(playerinfo.frame);
+    if playerinfo.flags.contains(PFTypes::MSEC) {
+        size += message.write_u8(playerinfo.msec.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::COMMAND) {
+        size += playerinfo.command.clone().unwrap_or_default().write(message);
+    }
+    if playerinfo.flags.contains(PFTypes::VELOCITY1) {
+        size += message.write_i16(playerinfo.velocity.x.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::VELOCITY2) {
+        size += message.write_i16(playerinfo.velocity.y.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::VELOCITY3) {
+        size += message.write_i16(playerinfo.velocity.z.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::MODEL) {
+        size += message.write_u8(playerinfo.model.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::SKINNUM) {
+        size += message.write_u8(playerinfo.skinnum.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::EFFECTS) {
+        size += message.write_u8(playerinfo.effects.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::WEAPONFRAME) {
+        size += message.write_u8(playerinfo.weaponframe.unwrap_or_default());
+    }
+    if playerinfo.flags.contains(PFTypes::TRANS) && message.flags.fte_protocol_extensions.contains(FteProtocolExtensions::TRANS) {
+        size += message.write_u8(playerinfo.alpha.unwrap_or_default());
+    }
+    Ok(size)
+}
+
 impl Playerinfo  {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         if message.r#type == MessageType::Connection {
             playerinfo_read_connection(message)
         } else {
-            playerinfo_read_demo(message)
+            Ok(ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd::read(message)?)))
+        }
+    }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        match self {
+            Playerinfo::PlayerinfoMvdT(playerinfo) => playerinfo.write(message),
+            Playerinfo::PlayerinfoConnectionT(playerinfo) => playerinfo_write_connection(message, playerinfo),
         }
     }
 }
@@ -850,7 +967,7 @@ bitflags! {
 
 bitflags! {
 #[derive(Serialize)]
-    struct UpdateTypes: u16 {
+    pub struct UpdateTypes: u16 {
         const ORIGIN1  = 1 << 9;
         const ORIGIN2  = 1 << 10;
         const ORIGIN3  = 1 << 11;
@@ -886,18 +1003,91 @@ pub struct Packetentity {
     pub transparency: Option<u8>,
 }
 
+impl Packetentity {
+    /// writes the entity the way [`Packetentities`] and [`Deltapacketentities`] read it
+    fn write(&self, message: &mut Message) -> usize {
+        let mut size: usize = 0;
+        let flags = UpdateTypes::from_bits_truncate(self.bits);
+        size += message.write_u16((self.bits & !511 & 0xff00) | (self.entity_index & 511));
+        if flags.contains(UpdateTypes::MOREBITS) {
+            size += message.write_u8((self.bits & 0xff) as u8);
+        }
+        size += self.write_fields(message, flags);
+        size
+    }
+
+    fn write_fields(&self, message: &mut Message, flags: UpdateTypes) -> usize {
+        let mut size: usize = 0;
+        if flags.contains(UpdateTypes::MODEL) {
+            size += message.write_u8((self.model.unwrap_or_default() & 0xff) as u8);
+        }
+        if flags.contains(UpdateTypes::FRAME) {
+            size += message.write_u8(self.frame.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::COLORMAP) {
+            size += message.write_u8(self.colormap.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::SKIN) {
+            size += message.write_u8(self.skin.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::EFFECTS) {
+            size += message.write_u8(self.effects.unwrap_or_default());
+        }
+        let origin = self.origin.unwrap_or(CoordinateVectorOption::empty());
+        let angle = self.angle.unwrap_or(AngleVectorOption::empty());
+        if flags.contains(UpdateTypes::ORIGIN1) {
+            size += message.write_entity_coordinate(origin.x.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::ANGLE1) {
+            size += message.write_angle(angle.x.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::ORIGIN2) {
+            size += message.write_entity_coordinate(origin.y.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::ANGLE2) {
+            size += message.write_angle(angle.y.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::ORIGIN3) {
+            size += message.write_entity_coordinate(origin.z.unwrap_or_default());
+        }
+        if flags.contains(UpdateTypes::ANGLE3) {
+            size += message.write_angle(angle.z.unwrap_or_default());
+        }
+        size
+    }
+
+    /// writes the entity the way [`SpawnstaticFte2`] and [`FteSpawnbaseline2`] read it
+    fn write_fte(&self, message: &mut Message) -> usize {
+        let mut size: usize = 0;
+        let flags = UpdateTypes::from_bits_truncate(self.bits);
+        size += message.write_u16((self.bits & !511 & 0xff00) | (self.entity_index & 511));
+        if flags.contains(UpdateTypes::MOREBITS) {
+            size += message.write_u8((self.bits & 0xff) as u8);
+        }
+        if flags.contains(UpdateTypes::from_bits_truncate(FteDeltaCheck::EVENMORE.bits())) {
+            size += message.write_u8((self.ftebits.bits() & 0xff) as u8);
+            if self.ftebits.contains(FteDeltaExtension::YETMORE) {
+                size += message.write_u8((self.ftebits.bits() >> 8) as u8);
+            }
+        }
+        size += self.write_fields(message, flags);
+        if self.ftebits.contains(FteDeltaExtension::TRANS) {
+            size += message.write_u8(self.transparency.unwrap_or_default());
+        }
+        size
+    }
+}
+
 #[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
 pub struct Packetentities {
     pub entities: Vec<Packetentity>
 }
 
 impl Packetentities  {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         trace_start!(message, false);
         let mut entities = Vec::new();
-        let mut i = 0;
         loop {
-            i += 1;
             trace_annotate!(message, "bits");
             let mut bits = message.read_u16(false)?;
             if bits == 0 {
@@ -961,7 +1151,7 @@ impl Packetentities  {
 
             if flags.contains(UpdateTypes::ORIGIN1) {
                 trace_annotate!(message, "origin.x");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.x = Some(tmp);
             }
 
@@ -973,7 +1163,7 @@ impl Packetentities  {
 
             if flags.contains(UpdateTypes::ORIGIN2) {
                 trace_annotate!(message, "origin.y");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.y = Some(tmp);
             }
 
@@ -985,7 +1175,7 @@ impl Packetentities  {
 
             if flags.contains(UpdateTypes::ORIGIN3) {
                 trace_annotate!(message, "origin.z");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.z = Some(tmp);
             }
 
@@ -1018,22 +1208,31 @@ impl Packetentities  {
                 ..Default::default()
             };
             entities.push(p);
-            // TODO: wha?
-            if i == 65 {
-                panic!();
-            }
         }
         let r = ServerMessage::Packetentities(Packetentities{ entities });
         trace_stop!(message, r);
         Ok(r)
     }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        let mut size: usize = 0;
+        for entity in &self.entities {
+            size += entity.write(message);
+        }
+        size += message.write_u16(0_u16);
+        Ok(size)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Serialize, Clone)]
 pub struct Bad {
 }
 impl Bad {
-    pub fn read(_message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(_message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
+        Err(MessageError::BadRead)
+    }
+
+    pub fn write(&self, _message: &mut Message) -> Result<usize, MessageError> {
         Err(MessageError::BadRead)
     }
 }
@@ -1045,7 +1244,7 @@ pub struct FteSpawnbaseline2 {
 }
 
 impl FteSpawnbaseline2 {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         trace_start!(message, false);
         let from = 0;
 
@@ -1134,7 +1333,7 @@ impl FteSpawnbaseline2 {
             if flags.contains(UpdateTypes::ORIGIN1) {
                 #[cfg(feature = "trace")]
                 message.read_trace_annotate("origin1");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.x = Some(tmp);
             }
 
@@ -1148,7 +1347,7 @@ impl FteSpawnbaseline2 {
             if flags.contains(UpdateTypes::ORIGIN2) {
                 #[cfg(feature = "trace")]
                 message.read_trace_annotate("origin2");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.y = Some(tmp);
             }
 
@@ -1162,7 +1361,7 @@ impl FteSpawnbaseline2 {
             if flags.contains(UpdateTypes::ORIGIN3) {
                 #[cfg(feature = "trace")]
                 message.read_trace_annotate("origin3");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.z = Some(tmp);
             }
 
@@ -1215,13 +1414,17 @@ impl FteSpawnbaseline2 {
                 transparency,
             };
 
-        let v = ServerMessage::SpawnstaticFte2(SpawnstaticFte2{
+        let v = ServerMessage::FteSpawnbaseline2(FteSpawnbaseline2{
             from,
             entity: p,
         });
         trace_stop!(message, v);
         Ok(v)
     }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        Ok(self.entity.write_fte(message))
+    }
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
@@ -1231,7 +1434,7 @@ pub struct SpawnstaticFte2 {
 }
 
 impl SpawnstaticFte2  {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         trace_start!(message, false);
         let from = 0;
 
@@ -1310,7 +1513,7 @@ impl SpawnstaticFte2  {
 
         if flags.contains(UpdateTypes::ORIGIN1) {
             trace_annotate!(message, "origin1");
-            let tmp = message.read_coordinate(false)?;
+            let tmp = message.read_entity_coordinate(false)?;
             origin_internal.x = Some(tmp);
         }
 
@@ -1322,7 +1525,7 @@ impl SpawnstaticFte2  {
 
         if flags.contains(UpdateTypes::ORIGIN2) {
             trace_annotate!(message, "origin2");
-            let tmp = message.read_coordinate(false)?;
+            let tmp = message.read_entity_coordinate(false)?;
             origin_internal.y = Some(tmp);
         }
 
@@ -1334,7 +1537,7 @@ impl SpawnstaticFte2  {
 
         if flags.contains(UpdateTypes::ORIGIN3) {
             trace_annotate!(message, "origin3");
-            let tmp = message.read_coordinate(false)?;
+            let tmp = message.read_entity_coordinate(false)?;
             origin_internal.z = Some(tmp);
         }
 
@@ -1392,6 +1595,10 @@ impl SpawnstaticFte2  {
         trace_stop!(message, v);
         Ok(v)
     }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        Ok(self.entity.write_fte(message))
+    }
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
@@ -1402,7 +1609,7 @@ pub struct Deltapacketentities {
 }
 
 impl Deltapacketentities  {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
         trace_start!(message, false);
         let mut entities = Vec::new();
         trace_annotate!(message, "from");
@@ -1475,7 +1682,7 @@ impl Deltapacketentities  {
 
             if flags.contains(UpdateTypes::ORIGIN1) {
                 trace_annotate!(message, "origin1");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.x = Some(tmp);
             }
 
@@ -1487,8 +1694,8 @@ impl Deltapacketentities  {
 
             if flags.contains(UpdateTypes::ORIGIN2) {
                 trace_annotate!(message, "origin2");
-                let tmp = message.read_coordinate(false)?;
-                origin_internal.z = Some(tmp);
+                let tmp = message.read_entity_coordinate(false)?;
+                origin_internal.y = Some(tmp);
             }
 
             if flags.contains(UpdateTypes::ANGLE2) {
@@ -1499,7 +1706,7 @@ impl Deltapacketentities  {
 
             if flags.contains(UpdateTypes::ORIGIN3) {
                 trace_annotate!(message, "origin3");
-                let tmp = message.read_coordinate(false)?;
+                let tmp = message.read_entity_coordinate(false)?;
                 origin_internal.z = Some(tmp);
             }
 
@@ -1539,77 +1746,45 @@ impl Deltapacketentities  {
         trace_stop!(message, v);
         Ok(v)
     }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        let mut size: usize = 0;
+        size += message.write_u8(self.from);
+        for entity in &self.entities {
+            size += entity.write(message);
+        }
+        size += message.write_u16(0_u16);
+        Ok(size)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Setinfo {
+pub struct Setinfo<'a> {
     pub player_number: u8,
-    pub key: StringByte,
-    pub value: StringByte,
+    pub key: StringByte<'a>,
+    pub value: StringByte<'a>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
-pub struct Print {
+pub struct Print<'a> {
     pub from: u8,
-    pub message: StringByte,
+    pub message: StringByte<'a>,
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
+#[qw(order = "channel, volume, attenuation, entity, index, origin")]
 pub struct Sound {
     pub channel: u16,
+    #[qw(value = "(channel >> 3) & 1023")]
     pub entity: u16,
     pub index: u8,
+    #[qw(if = "channel & 1 << 15 != 0")]
     pub volume: Option<u8>,
+    #[qw(if = "channel & 1 << 14 != 0")]
     pub attenuation: Option<u8>,
     pub origin: CoordinateVector
 }
 
-impl Sound {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
-        trace_start!(message, false);
-        trace_annotate!(message, "channel");
-        let channel = message.read_u16(false)?;
-        let mut volume = None;
-        let mut attenuation = None;
-        let mut origin = CoordinateVector{..Default::default()};
-
-        if channel & 1 << 15 == 1 << 15 {
-            trace_annotate!(message, "volume");
-            let b = message.read_u8(false)?;
-            volume  = Some(b);
-        }
-
-        if channel & 1 << 14 == 1 << 14 {
-            trace_annotate!(message, "channel");
-            let b = message.read_u8(false)?;
-            attenuation = Some(b);
-        }
-
-        let entity  = (channel >> 3) & 1023;
-
-        trace_annotate!(message, "index");
-        let index = message.read_u8(false)?;
-
-        trace_annotate!(message, "origin.x");
-        origin.x = message.read_coordinate(false)?;
-        trace_annotate!(message, "origin.y");
-        origin.y = message.read_coordinate(false)?;
-        trace_annotate!(message, "origin.x");
-        origin.z = message.read_coordinate(false)?;
-
-        let r = ServerMessage::Sound(Sound{
-            channel,
-            entity,
-            index,
-            volume,
-            attenuation,
-            origin
-        });
-        trace_stop!(message, r);
-        Ok(r)
-    }
-}
-
 #[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
 pub struct Damage {
     pub armor: u8,
@@ -1652,11 +1827,263 @@ pub struct Intermission {
     pub angle: AngleVector,
 }
 
+#[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Maxspeed {
+    pub maxspeed: f32,
+}
+
+#[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Entgravity {
+    pub gravity: f32,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Stopsound {
+    pub channel: u16,
+    #[qw(value = "channel >> 3")]
+    pub entity: u16,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Setpause {
+    pub paused: u8,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Killedmonster {
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Foundsecret {
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Finale<'a> {
+    pub text: StringByte<'a>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, ParseMessage, Serialize, Clone)]
+pub struct Sellscreen {
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Serialize, Clone)]
 pub struct Disconnect {
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, TryFromPrimitive, Display, Serialize, Clone)]
+impl Disconnect {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        let v = ServerMessage::Disconnect(Disconnect{});
+        trace_stop!(message, v);
+        Ok(v)
+    }
+
+    /// in mvds the disconnect is followed by the "EndOfDemo" marker
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        if message.r#type == MessageType::Mvd {
+            return Ok(message.write_bytes(b"EndOfDemo\0"));
+        }
+        Ok(0)
+    }
+}
+
+/// size of a chunk with [`FteProtocolExtensions::CHUNKEDDOWNLOADS`]
+pub const DOWNLOAD_CHUNK_SIZE: usize = 1024;
+
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone, Default)]
+pub struct Download<'a> {
+    /// only set with chunked downloads, -1 if the message carries the size and name of the file
+    pub chunk: Option<i32>,
+    /// size of the data, -1 if the file is not available, -2 if the file was renamed.
+    /// with chunked downloads the size of the whole file
+    pub size: i32,
+    pub percent: u8,
+    pub name: Option<StringByte<'a>>,
+    pub data: Vec<u8>,
+}
+
+impl Download<'_> {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        let mut download = Download::default();
+        if message.flags.fte_protocol_extensions.contains(FteProtocolExtensions::CHUNKEDDOWNLOADS) {
+            trace_annotate!(message, "chunk");
+            let chunk = message.read_i32(false)?;
+            download.chunk = Some(chunk);
+            if chunk < 0 {
+                trace_annotate!(message, "size");
+                download.size = message.read_i32(false)?;
+                trace_annotate!(message, "name");
+                download.name = Some(message.read_stringbyte(false)?);
+            } else {
+                trace_annotate!(message, "data");
+                download.data = message.read_bytes(DOWNLOAD_CHUNK_SIZE as u32, false)?;
+                download.size = DOWNLOAD_CHUNK_SIZE as i32;
+            }
+        } else {
+            trace_annotate!(message, "size");
+            download.size = message.read_i16(false)? as i32;
+            trace_annotate!(message, "percent");
+            download.percent = message.read_u8(false)?;
+            if download.size == -2 {
+                trace_annotate!(message, "name");
+                download.name = Some(message.read_stringbyte(false)?);
+            } else if download.size > 0 {
+                trace_annotate!(message, "data");
+                download.data = message.read_bytes(download.size as u32, false)?;
+            }
+        }
+        let v = ServerMessage::Download(download);
+        trace_stop!(message, v);
+        Ok(v)
+    }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        let mut size: usize = 0;
+        if message.flags.fte_protocol_extensions.contains(FteProtocolExtensions::CHUNKEDDOWNLOADS) {
+            let chunk = self.chunk.unwrap_or(-1);
+            size += message.write_i32(chunk);
+            if chunk < 0 {
+                size += message.write_i32(self.size);
+                size += message.write_stringbyte(&self.name.clone().unwrap_or_default());
+            } else {
+                let mut data = self.data.clone();
+                data.resize(DOWNLOAD_CHUNK_SIZE, 0);
+                size += message.write_bytes(&data);
+            }
+        } else {
+            size += message.write_i16(self.size as i16);
+            size += message.write_u8(self.percent);
+            if self.size == -2 {
+                size += message.write_stringbyte(&self.name.clone().unwrap_or_default());
+            } else if self.size > 0 {
+                size += message.write_bytes(&self.data);
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone, Copy, Default)]
+pub struct Projectile {
+    /// only sent with [`Nails2`]
+    pub number: Option<u8>,
+    pub origin: CoordinateVector,
+    /// only pitch and yaw are sent
+    pub angle: AngleVector,
+}
+
+impl Projectile {
+    /// reads the packed 48 bits: 12 bits per coordinate, 4 bits pitch, 8 bits yaw
+    pub fn read(message: &mut Message, numbered: bool) -> Result<Projectile, MessageError> {
+        trace_start!(message, false);
+        let mut number = None;
+        if numbered {
+            trace_annotate!(message, "number");
+            number = Some(message.read_u8(false)?);
+        }
+        trace_annotate!(message, "bits");
+        let bits = message.read_bytes(6, false)?;
+        let unpack = |low: u16| (((low & 4095) as i32) << 1) as f32 - 4096.0;
+        let origin = CoordinateVector{
+            x: unpack(bits[0] as u16 | (bits[1] as u16) << 8),
+            y: unpack((bits[1] >> 4) as u16 | (bits[2] as u16) << 4),
+            z: unpack(bits[3] as u16 | (bits[4] as u16) << 8),
+        };
+        let angle = AngleVector{
+            x: 360.0 * (bits[4] >> 4) as f32 / 16.0,
+            y: 360.0 * bits[5] as f32 / 256.0,
+            z: 0.0,
+        };
+        let v = Projectile{
+            number,
+            origin,
+            angle,
+        };
+        trace_stop!(message);
+        Ok(v)
+    }
+
+    pub fn write(&self, message: &mut Message) -> usize {
+        let mut size: usize = 0;
+        if let Some(number) = self.number {
+            size += message.write_u8(number);
+        }
+        // truncated like the server does it
+        let pack = |c: f32| ((c + 4096.0) as i32 >> 1) & 4095;
+        let (x, y, z) = (pack(self.origin.x), pack(self.origin.y), pack(self.origin.z));
+        let pitch = (self.angle.x * 16.0 / 360.0) as i32 & 15;
+        let yaw = (self.angle.y * 256.0 / 360.0) as i32 & 255;
+        size += message.write_bytes(&[
+            x as u8,
+            ((x >> 8) | (y << 4)) as u8,
+            (y >> 4) as u8,
+            z as u8,
+            ((z >> 8) | (pitch << 4)) as u8,
+            yaw as u8,
+        ]);
+        size
+    }
+}
+
+fn read_projectiles(message: &mut Message, numbered: bool) -> Result<Vec<Projectile>, MessageError> {
+    trace_annotate!(message, "count");
+    let count = message.read_u8(false)?;
+    let mut projectiles = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        projectiles.push(Projectile::read(message, numbered)?);
+    }
+    Ok(projectiles)
+}
+
+fn write_projectiles(message: &mut Message, projectiles: &[Projectile]) -> usize {
+    let mut size = message.write_u8(projectiles.len() as u8);
+    for projectile in projectiles {
+        size += projectile.write(message);
+    }
+    size
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+pub struct Nails {
+    pub projectiles: Vec<Projectile>,
+}
+
+impl Nails {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        let projectiles = read_projectiles(message, false)?;
+        let v = ServerMessage::Nails(Nails{ projectiles });
+        trace_stop!(message, v);
+        Ok(v)
+    }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        Ok(write_projectiles(message, &self.projectiles))
+    }
+}
+
+/// same as [`Nails`] but every projectile carries a number, used in mvds
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+pub struct Nails2 {
+    pub projectiles: Vec<Projectile>,
+}
+
+impl Nails2 {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ServerMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        let projectiles = read_projectiles(message, true)?;
+        let v = ServerMessage::Nails2(Nails2{ projectiles });
+        trace_stop!(message, v);
+        Ok(v)
+    }
+
+    pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+        Ok(write_projectiles(message, &self.projectiles))
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, TryFromPrimitive, Display, Serialize, Clone, Copy)]
 #[repr(u8)]
 pub enum TempEntityType {
     Spike = 0,
@@ -1675,64 +2102,21 @@ pub enum TempEntityType {
     LightningBlood
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, ParseMessage, Serialize, Clone)]
+#[qw(order = "r#type, count, entity, start, origin")]
 pub struct Tempentity {
+    #[qw(try_from = "u8")]
     pub r#type: TempEntityType,
     pub origin: CoordinateVector,
+    #[qw(if = "r#type == TempEntityType::Lightning1 || r#type == TempEntityType::Lightning2 || r#type == TempEntityType::Lightning3")]
     pub start: CoordinateVector,
+    #[qw(if = "r#type == TempEntityType::Lightning1 || r#type == TempEntityType::Lightning2 || r#type == TempEntityType::Lightning3")]
     pub entity: u16,
-    pub count: i8
-}
-
-impl Tempentity {
-    pub fn read(message: &mut Message) -> Result<ServerMessage, MessageError> {
-        trace_start!(message, false);
-        trace_annotate!(message, "entity type");
-        let t = message.read_u8(false)?;
-        let r#type = TempEntityType::try_from(t)?;
-
-        let mut count = 0_i8;
-        if r#type == TempEntityType::Gunshot || r#type == TempEntityType::Blood {
-            trace_annotate!(message, "count");
-            count = message.read_i8(false)?;
-        }
-
-        let mut entity = 0_u16;
-        let mut start =  CoordinateVector{..Default::default()};
-        if r#type == TempEntityType::Lightning1
-            || r#type == TempEntityType::Lightning2 
-                || r#type == TempEntityType::Lightning3
-                {
-                    trace_annotate!(message, "entity");
-                    entity = message.read_u16(false)?;
-                    trace_annotate!(message, "start.x");
-                    start.x = message.read_coordinate(false)?;
-                    trace_annotate!(message, "start.y");
-                    start.y = message.read_coordinate(false)?;
-                    trace_annotate!(message, "start.z");
-                    start.z = message.read_coordinate(false)?;
-                }
-
-        let mut origin =  CoordinateVector{..Default::default()};
-        trace_annotate!(message, "origin.x");
-        origin.x = message.read_coordinate(false)?;
-        trace_annotate!(message, "origin.y");
-        origin.y = message.read_coordinate(false)?;
-        trace_annotate!(message, "origin.z");
-        origin.z = message.read_coordinate(false)?;
-        let r = ServerMessage::Tempentity(Tempentity{
-            r#type,
-            origin,
-            start,
-            entity,
-            count,
-        });
-        trace_stop!(message, r);
-        Ok(r)
-    }
+    #[qw(if = "r#type == TempEntityType::Gunshot || r#type == TempEntityType::Blood")]
+    pub count: i8,
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, TryFromPrimitive, Display, Serialize)]
+#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, TryFromPrimitive, Display, Serialize, Clone, Copy)]
 #[repr(u8)]
 pub enum ClientServer {
     Bad = 0,
@@ -1823,32 +2207,162 @@ pub struct ConnectionLessServerChallenge {
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Default, Serialize, Clone)]
-pub struct Connected {
+pub struct Connected<'a> {
     pub sequence: u32,
     pub sequence_ack: u32,
-    pub messages: Vec<ServerMessage>,
+    pub messages: Vec<ServerMessage<'a>>,
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Display, Serialize, Clone)]
-pub enum Packet {
+pub enum Packet<'a> {
     Error,
     ConnectionLessServerChallenge(ConnectionLessServerChallenge),
     ConnectionLessServerConnection,
-    Connected(Connected)
+    Connected(Connected<'a>)
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+pub struct ClientMove {
+    pub checksum: u8,
+    pub lossage: u8,
+    /// the commands are deltas from the one before, the first one from an empty command
+    pub commands: [DeltaUserCommand; 3],
+}
+
+impl ClientMove {
+    /// the checksum covers everything after it and is seeded with the packets sequence
+    pub fn read<'a>(message: &mut Message<'a>, sequence: u32) -> Result<ClientMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        trace_annotate!(message, "checksum");
+        let checksum = message.read_u8(false)?;
+        let start = message.start + message.position;
+        trace_annotate!(message, "lossage");
+        let lossage = message.read_u8(false)?;
+        trace_annotate!(message, "oldest");
+        let oldest = DeltaUserCommand::read(message)?;
+        trace_annotate!(message, "old");
+        let old = DeltaUserCommand::read(message)?;
+        trace_annotate!(message, "new");
+        let new = DeltaUserCommand::read(message)?;
+        let stop = message.start + message.position;
+
+        let calculated = (generate_checksum(&message.buffer[start..stop], 0, stop - start, sequence & !(1 << 31)) & 0xff) as u8;
+        if calculated != checksum {
+            trace_abort!(message);
+            return Err(MessageError::ChecksumMismatch(checksum, calculated));
+        }
+
+        let v = ClientMessage::Move(ClientMove{
+            checksum,
+            lossage,
+            commands: [oldest, old, new],
+        });
+        trace_stop!(message, v);
+        Ok(v)
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Serialize, Clone)]
+pub struct ClientUpload {
+    pub size: i16,
+    pub percent: u8,
+    pub data: Vec<u8>,
+}
+
+impl ClientUpload {
+    pub fn read<'a>(message: &mut Message<'a>) -> Result<ClientMessage<'a>, MessageError> {
+        trace_start!(message, false);
+        trace_annotate!(message, "size");
+        let size = message.read_i16(false)?;
+        trace_annotate!(message, "percent");
+        let percent = message.read_u8(false)?;
+        trace_annotate!(message, "data");
+        let data = message.read_bytes(size.max(0) as u32, false)?;
+        let v = ClientMessage::Upload(ClientUpload{
+            size,
+            percent,
+            data,
+        });
+        trace_stop!(message, v);
+        Ok(v)
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Display, Serialize, Clone)]
+pub enum ClientMessage<'a> {
+    Nop,
+    Move(ClientMove),
+    StringCommand(StringByte<'a>),
+    /// the sequence of the frame the server should delta entities from
+    Delta(u8),
+    TMove(CoordinateVector),
+    Upload(ClientUpload),
+}
+
+impl ClientServer {
+    pub fn read_message<'a>(self, message: &mut Message<'a>, sequence: u32) -> Result<ClientMessage<'a>, MessageError> {
+        match self {
+            ClientServer::Nop => Ok(ClientMessage::Nop),
+            ClientServer::Move => ClientMove::read(message, sequence),
+            ClientServer::StringCommand => {
+                trace_annotate!(message, "command");
+                Ok(ClientMessage::StringCommand(message.read_stringbyte(false)?))
+            },
+            ClientServer::Delta => {
+                trace_annotate!(message, "sequence");
+                Ok(ClientMessage::Delta(message.read_u8(false)?))
+            },
+            ClientServer::TMove => {
+                trace_annotate!(message, "origin");
+                Ok(ClientMessage::TMove(message.read_coordinatevector(false)?))
+            },
+            ClientServer::Upload => ClientUpload::read(message),
+            _ => Err(MessageError::UnhandledClientType(self)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Default, Serialize, Clone)]
+pub struct ClientConnected<'a> {
+    pub sequence: u32,
+    pub sequence_ack: u32,
+    pub qport: u16,
+    pub messages: Vec<ClientMessage<'a>>,
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Display, Serialize, Clone)]
+pub enum ClientPacket<'a> {
+    /// everything after the 0xffffffff header
+    ConnectionLess(Vec<u8>),
+    Connected(ClientConnected<'a>),
 }
 
 macro_rules! initialize_message_type {
-    ($($ty:ident), *) => {
+    ($($ty:ident $(<$lt:lifetime>)?), *) => {
         paste! {
             #[derive(Debug, PartialEq, PartialOrd, Display, Serialize, Clone)]
             #[non_exhaustive]
-            pub enum ServerMessage {
+            pub enum ServerMessage<'a> {
                 $(
-                [< $ty >]([< $ty >]),
+                [< $ty >]([< $ty >]$(<$lt>)?),
                 )*
             }
+        impl ServerMessage<'_> {
+            /// writes the message type followed by the message
+            pub fn write(&self, message: &mut Message) -> Result<usize, MessageError> {
+                match self {
+                    $(
+                        ServerMessage::[<$ty>](v) => {
+                            let size = message.write_u8(ServerClient::[<$ty>] as u8);
+                            Ok(size + v.write(message)?)
+                        }
+                     )*
+                }
+            }
+        }
+
         impl ServerClient {
-            pub fn  read_message  (self,  message: &mut Message) ->  Result<ServerMessage, MessageError> {
+            pub fn  read_message<'a>(self,  message: &mut Message<'a>) ->  Result<ServerMessage<'a>, MessageError> {
                 match self {
                     $(
                         ServerClient::[<$ty>] => {
@@ -1866,6 +2380,410 @@ macro_rules! initialize_message_type {
     }
 }
 
-initialize_message_type!(Serverdata, Soundlist, Modellist,Cdtrack, Stufftext, Spawnstatic,Spawnbaseline, Spawnstaticsound, Updatefrags, Updateping, Updatepl, Updateentertime, Updateuserinfo, Playerinfo, Updatestatlong, Updatestat, Lightstyle, Serverinfo, Centerprint, Packetentities, Deltapacketentities, Tempentity, Setinfo, Print, Sound, Damage, Setangle, Smallkick, Bigkick, Muzzleflash, Chokecount, Intermission, Disconnect, Setview, SpawnstaticFte2, Bad, FteSpawnbaseline2);
+initialize_message_type!(Serverdata<'a>, Soundlist<'a>, Modellist<'a>,Cdtrack, Stufftext<'a>, Spawnstatic,Spawnbaseline, Spawnstaticsound, Updatefrags, Updateping, Updatepl, Updateentertime, Updateuserinfo<'a>, Playerinfo, Updatestatlong, Updatestat, Lightstyle<'a>, Serverinfo<'a>, Centerprint<'a>, Packetentities, Deltapacketentities, Tempentity, Setinfo<'a>, Print<'a>, Sound, Damage, Setangle, Smallkick, Bigkick, Muzzleflash, Chokecount, Intermission, Disconnect, Setview, SpawnstaticFte2, Bad, FteSpawnbaseline2, Nails, Nails2, Download<'a>, Maxspeed, Entgravity, Stopsound, Setpause, Killedmonster, Foundsecret, Finale<'a>, Sellscreen);
+
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::message::{Message, MessageFlags, MessageType};
+    use crate::protocol::types::*;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    fn new_message(buffer: Vec<u8>, flags: MessageFlags, r#type: MessageType) -> Message<'static> {
+        let length = buffer.len();
+        Message::new(buffer, 0, length, false, flags,
+#[cfg(feature = "ascii_strings")]
+            None,
+            r#type)
+    }
+
+    // writes the messages, reads them back and writes them again
+    fn round_trip(r#type: MessageType, flags: MessageFlags, messages: Vec<ServerMessage>) {
+        for server_message in messages {
+            let mut message = new_message(vec![], flags, r#type);
+            let size = server_message.write(&mut message).unwrap();
+            let bytes = message.buffer.to_vec();
+            assert_eq!(size, bytes.len(), "{:?}", server_message);
+
+            let mut message = new_message(bytes.clone(), flags, r#type);
+            let cmd = ServerClient::try_from(message.read_u8(false).unwrap()).unwrap();
+            let read = cmd.read_message(&mut message).unwrap();
+            assert_eq!(message.position, bytes.len(), "{:?}", server_message);
+            assert_eq!(read, server_message);
+
+            let mut message = new_message(vec![], flags, r#type);
+            read.write(&mut message).unwrap();
+            assert_eq!(*message.buffer, bytes, "{:?}", server_message);
+        }
+    }
+
+    fn packetentity(entity_index: u16) -> Packetentity {
+        Packetentity{
+            entity_index,
+            bits: (UpdateTypes::MOREBITS | UpdateTypes::ORIGIN1 | UpdateTypes::ORIGIN2 | UpdateTypes::ANGLE2 |
+                UpdateTypes::FRAME | UpdateTypes::MODEL | UpdateTypes::SKIN | UpdateTypes::EFFECTS).bits(),
+            model: Some(12),
+            frame: Some(3),
+            skin: Some(1),
+            effects: Some(64),
+            origin: Some(CoordinateVectorOption{ x: Some(-512.125), y: Some(33.5), z: None }),
+            angle: Some(AngleVectorOption{ x: None, y: Some(270.0), z: None }),
+            ..Default::default()
+        }
+    }
+
+    fn messages(ascii_converter: &AsciiConverter) -> Vec<ServerMessage<'static>> {
+        let sb = |s: &str| ascii_converter.convert_to_stringbyte(s);
+        vec![
+            ServerMessage::Soundlist(Soundlist{ start: 0, sounds: vec![sb("weapons/r_exp3.wav"), sb("player/land.wav")], offset: 2 }),
+            ServerMessage::Modellist(Modellist{ start: 0, models: vec![sb("maps/dm2.bsp")], offset: 0 }),
+            ServerMessage::Cdtrack(Cdtrack{ track: 4 }),
+            ServerMessage::Stufftext(Stufftext{ text: sb("fullserverinfo \\\\maxfps\\\\77\n") }),
+            ServerMessage::Spawnstatic(Spawnstatic{
+                model_index: 3, model_frame: 1, colormap: 0, skinnum: 2,
+                origin: CoordinateVector{ x: 1.0, y: -2.5, z: 100.0 },
+                angle: AngleVector{ x: 0.0, y: 90.0, z: 180.0 },
+            }),
+            ServerMessage::Spawnbaseline(Spawnbaseline{
+                index: 300, model_index: 3, model_frame: 1, colormap: 0, skinnum: 2,
+                origin: CoordinateVector{ x: 1.0, y: -2.5, z: 100.0 },
+                angle: AngleVector{ x: 0.0, y: 90.0, z: 180.0 },
+            }),
+            ServerMessage::Spawnstaticsound(Spawnstaticsound{ origin: CoordinateVector{ x: 8.0, y: 16.0, z: 24.0 }, index: 5, volume: 255, attenuation: 3 }),
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 3, frags: -5 }),
+            ServerMessage::Updateping(Updateping{ player_number: 3, ping: 25 }),
+            ServerMessage::Updatepl(Updatepl{ player_number: 3, pl: 2 }),
+            ServerMessage::Updateentertime(Updateentertime{ player_number: 3, entertime: 12.5 }),
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 3, uid: 1234, userinfo: sb("\\\\name\\\\player\\\\team\\\\red") }),
+            ServerMessage::Updatestatlong(Updatestatlong{ stat: 15, value: -100_000 }),
+            ServerMessage::Updatestat(Updatestat{ stat: 0, value: -3 }),
+            ServerMessage::Lightstyle(Lightstyle{ index: 1, style: sb("mmnmmommommnonmmonqnmmo") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: sb("hostname"), value: sb("test server") }),
+            ServerMessage::Centerprint(Centerprint{ message: sb("match starts in 5 seconds") }),
+            ServerMessage::Packetentities(Packetentities{ entities: vec![packetentity(1), packetentity(511)] }),
+            ServerMessage::Deltapacketentities(Deltapacketentities{ from: 27, entities: vec![
+                packetentity(64),
+                Packetentity{ entity_index: 65, bits: UpdateTypes::REMOVE.bits(), remove: true, ..Default::default() },
+            ]}),
+            ServerMessage::Tempentity(Tempentity{ r#type: TempEntityType::Explosion, origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 }, start: CoordinateVector::default(), entity: 0, count: 0 }),
+            ServerMessage::Tempentity(Tempentity{ r#type: TempEntityType::Gunshot, origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 }, start: CoordinateVector::default(), entity: 0, count: 7 }),
+            ServerMessage::Tempentity(Tempentity{ r#type: TempEntityType::Lightning2, origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 }, start: CoordinateVector{ x: -1.0, y: -2.0, z: -3.0 }, entity: 5, count: 0 }),
+            ServerMessage::Setinfo(Setinfo{ player_number: 1, key: sb("topcolor"), value: sb("4") }),
+            ServerMessage::Print(Print{ from: 3, message: sb("player: hello\n") }),
+            ServerMessage::Sound(Sound{ channel: (1 << 15) | (1 << 14) | (12 << 3) | 1, entity: 12, index: 8, volume: Some(200), attenuation: Some(64), origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 } }),
+            ServerMessage::Sound(Sound{ channel: (12 << 3) | 1, entity: 12, index: 8, volume: None, attenuation: None, origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 } }),
+            ServerMessage::Damage(Damage{ armor: 10, blood: 20, origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 } }),
+            ServerMessage::Nails(Nails{ projectiles: vec![
+                Projectile{ number: None, origin: CoordinateVector{ x: -4096.0, y: 1024.0, z: 4094.0 }, angle: AngleVector{ x: 45.0, y: 90.0, z: 0.0 } },
+                Projectile{ number: None, origin: CoordinateVector{ x: 2.0, y: -2.0, z: 0.0 }, angle: AngleVector{ x: 0.0, y: 358.59375, z: 0.0 } },
+            ] }),
+            ServerMessage::Download(Download{ chunk: None, size: 4, percent: 50, name: None, data: vec![1, 2, 3, 4] }),
+            ServerMessage::Download(Download{ chunk: None, size: -1, percent: 0, name: None, data: vec![] }),
+            ServerMessage::Nails2(Nails2{ projectiles: vec![
+                Projectile{ number: Some(7), origin: CoordinateVector{ x: 100.0, y: -300.0, z: 50.0 }, angle: AngleVector{ x: 337.5, y: 180.0, z: 0.0 } },
+            ] }),
+            ServerMessage::Setangle(Setangle{ index: 2, angle: AngleVector{ x: 45.0, y: 90.0, z: 0.0 } }),
+            ServerMessage::Setview(Setview{ setview: 3 }),
+            ServerMessage::Smallkick(Smallkick{}),
+            ServerMessage::Bigkick(Bigkick{}),
+            ServerMessage::Muzzleflash(Muzzleflash{ entity_index: 4 }),
+            ServerMessage::Chokecount(Chokecount{ chokecount: 9 }),
+            ServerMessage::Intermission(Intermission{ origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 }, angle: AngleVector{ x: 45.0, y: 90.0, z: 0.0 } }),
+            ServerMessage::Maxspeed(Maxspeed{ maxspeed: 320.0 }),
+            ServerMessage::Entgravity(Entgravity{ gravity: 0.5 }),
+            ServerMessage::Stopsound(Stopsound{ channel: (12 << 3) | 2, entity: 12 }),
+            ServerMessage::Setpause(Setpause{ paused: 1 }),
+            ServerMessage::Killedmonster(Killedmonster{}),
+            ServerMessage::Foundsecret(Foundsecret{}),
+            ServerMessage::Finale(Finale{ text: sb("congratulations") }),
+            ServerMessage::Sellscreen(Sellscreen{}),
+            ServerMessage::SpawnstaticFte2(SpawnstaticFte2{ from: 0, entity: Packetentity{
+                entity_index: 700,
+                bits: (UpdateTypes::MOREBITS | UpdateTypes::FTE_EXT | UpdateTypes::MODEL | UpdateTypes::ORIGIN3).bits(),
+                ftebits: FteDeltaExtension::ENTITYDOUBLE | FteDeltaExtension::TRANS,
+                model: Some(7),
+                origin: Some(CoordinateVectorOption{ x: None, y: None, z: Some(-64.0) }),
+                transparency: Some(128),
+                ..Default::default()
+            }}),
+            ServerMessage::FteSpawnbaseline2(FteSpawnbaseline2{ from: 0, entity: Packetentity{
+                entity_index: 12,
+                bits: (UpdateTypes::MOREBITS | UpdateTypes::FTE_EXT | UpdateTypes::MODEL).bits(),
+                ftebits: FteDeltaExtension::MODELDOUBLE | FteDeltaExtension::YETMORE | FteDeltaExtension::DRAWFLAGS,
+                model: Some(600),
+                ..Default::default()
+            }}),
+        ]
+    }
+
+    #[test]
+    fn derive_attributes() {
+        let playerinfo = PlayerinfoMvd{
+            player_number: 1,
+            flags: DfTypes::ORIGIN2 | DfTypes::ANGLE2 | DfTypes::MODEL,
+            frame: 2,
+            origin: Some(CoordinateVectorOption{ x: None, y: Some(8.0), z: None }),
+            angle: Some(AngleVectorOption{ x: None, y: Some(90.0), z: None }),
+            model: Some(5),
+            skinnum: None,
+            effects: None,
+            weaponframe: None,
+        };
+        let mut message = new_message(vec![], MessageFlags::default(), MessageType::Mvd);
+        playerinfo.write(&mut message).unwrap();
+        let mut expected = vec![1];
+        expected.extend(playerinfo.flags.bits().to_le_bytes());
+        // frame, origin.y as coord, angle.y as angle16, model
+        expected.extend([2, 64, 0, 0, 64, 5]);
+        assert_eq!(*message.buffer, expected);
+        let mut message = new_message(expected, MessageFlags::default(), MessageType::Mvd);
+        assert_eq!(PlayerinfoMvd::read(&mut message).unwrap(), playerinfo);
+
+        // volume is read before the sound index, but declared after it
+        let sound = Sound{
+            channel: 1 << 15 | 7 << 3 | 2,
+            entity: 7,
+            index: 4,
+            volume: Some(200),
+            attenuation: None,
+            origin: CoordinateVector{ x: 0.0, y: 0.0, z: 0.0 },
+        };
+        let mut message = new_message(vec![], MessageFlags::default(), MessageType::Mvd);
+        sound.write(&mut message).unwrap();
+        assert_eq!(message.buffer[..4], [sound.channel.to_le_bytes()[0], sound.channel.to_le_bytes()[1], 200, 4]);
+    }
+
+    #[test]
+    fn write_read_mvd() {
+        let ascii_converter = AsciiConverter::new();
+        let mut messages = messages(&ascii_converter);
+        messages.push(ServerMessage::Serverdata(Serverdata{
+            protocol: ProtocolVersion::Standard,
+            fte_protocol_extension: FteProtocolExtensions::TRANS | FteProtocolExtensions::CHUNKEDDOWNLOADS,
+            mvd_protocol_extension: MvdProtocolExtensions::HIGHLAGTELEPORT,
+            servercount: 3,
+            demotime: 12.75,
+            gamedir: ascii_converter.convert_to_stringbyte("qw"),
+            map: ascii_converter.convert_to_stringbyte("e1m2"),
+            movevars: [800.0, 4.0, 0.0, 320.0, 500.0, 10.0, 6.0, 100.0, 1.0, 2.0],
+            ..Default::default()
+        }));
+        messages.push(ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+            player_number: 4,
+            flags: DfTypes::ORIGIN2 | DfTypes::ANGLE | DfTypes::ANGLE3 | DfTypes::SKINNUM | DfTypes::EFFECTS | DfTypes::WEAPONFRAME,
+            frame: 13,
+            origin: Some(CoordinateVectorOption{ x: None, y: Some(-1000.0), z: None }),
+            angle: Some(AngleVectorOption{ x: Some(45.0), y: None, z: Some(270.0) }),
+            model: None,
+            skinnum: Some(1),
+            effects: Some(4),
+            weaponframe: Some(2),
+        })));
+        round_trip(MessageType::Mvd, MessageFlags::default(), messages);
+    }
+
+    #[test]
+    fn write_read_connection() {
+        let ascii_converter = AsciiConverter::new();
+        let flags = MessageFlags{
+            protocol: ProtocolVersion::Standard as u32,
+            fte_protocol_extensions: FteProtocolExtensions::TRANS,
+            ..Default::default()
+        };
+        let mut messages = messages(&ascii_converter);
+        messages.push(ServerMessage::Serverdata(Serverdata{
+            protocol: ProtocolVersion::Standard,
+            servercount: 3,
+            player_number: 130,
+            gamedir: ascii_converter.convert_to_stringbyte("qw"),
+            map: ascii_converter.convert_to_stringbyte("e1m2"),
+            ..Default::default()
+        }));
+        messages.push(ServerMessage::Disconnect(Disconnect{}));
+        messages.push(ServerMessage::Playerinfo(Playerinfo::PlayerinfoConnectionT(PlayerinfoConnection{
+            player_number: 1,
+            flags: PFTypes::MSEC | PFTypes::COMMAND | PFTypes::VELOCITY1 | PFTypes::VELOCITY3 | PFTypes::MODEL | PFTypes::WEAPONFRAME,
+            origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 },
+            frame: 5,
+            msec: Some(13),
+            command: Some(DeltaUserCommand{
+                bits: UserCommandFlags::ANGLE1 | UserCommandFlags::ANGLE2 | UserCommandFlags::FORWARD | UserCommandFlags::BUTTONS,
+                angle: AngleVectorOption{ x: Some(45.0), y: Some(90.0), z: None },
+                forward: Some(320),
+                buttons: Some(2),
+                msec: Some(13),
+                ..Default::default()
+            }),
+            velocity: VelocityVectorOption{ x: Some(-320), y: None, z: Some(270) },
+            model: Some(2),
+            skinnum: None,
+            effects: None,
+            weaponframe: Some(1),
+            alpha: None,
+        })));
+        round_trip(MessageType::Connection, flags, messages);
+
+        let flags = MessageFlags{
+            fte_protocol_extensions: FteProtocolExtensions::CHUNKEDDOWNLOADS,
+            ..flags
+        };
+        round_trip(MessageType::Connection, flags, vec![
+            ServerMessage::Download(Download{ chunk: Some(-1), size: 2000, percent: 0, name: Some(ascii_converter.convert_to_stringbyte("maps/dm3.bsp")), data: vec![] }),
+            ServerMessage::Download(Download{ chunk: Some(1), size: DOWNLOAD_CHUNK_SIZE as i32, percent: 0, name: None, data: vec![7; DOWNLOAD_CHUNK_SIZE] }),
+        ]);
+    }
 
+    #[test]
+    fn write_read_delta_usercommand_old_protocol() {
+        let flags = MessageFlags{ protocol: 26, ..Default::default() };
+        let command = DeltaUserCommand{
+            bits: UserCommandFlags::ANGLE2 | UserCommandFlags::FORWARD | UserCommandFlags::SIDE | UserCommandFlags::IMPULSE,
+            angle: AngleVectorOption{ x: None, y: Some(180.0), z: None },
+            forward: Some(-400),
+            side: Some(200),
+            impulse: Some(7),
+            msec: Some(12),
+            ..Default::default()
+        };
+        let mut message = new_message(vec![], flags, MessageType::Connection);
+        command.write(&mut message);
+        let bytes = message.buffer.to_vec();
+        let mut message = new_message(bytes, flags, MessageType::Connection);
+        assert_eq!(DeltaUserCommand::read(&mut message).unwrap(), command);
+    }
 
+    #[test]
+    fn read_client_packet() {
+        let flags = MessageFlags{ protocol: 28, ..Default::default() };
+        let command = DeltaUserCommand{
+            bits: UserCommandFlags::ANGLE2 | UserCommandFlags::FORWARD | UserCommandFlags::BUTTONS,
+            angle: AngleVectorOption{ x: None, y: Some(90.0), z: None },
+            forward: Some(400),
+            buttons: Some(1),
+            msec: Some(13),
+            ..Default::default()
+        };
+        let sequence = 5 | 1 << 31;
+        let mut message = new_message(vec![], flags, MessageType::Connection);
+        message.write_u32(sequence);
+        message.write_u32(3_u32);
+        message.write_u16(27001_u16);
+        message.write_client_command_string("new");
+        message.write_u8(ClientServer::Nop as u8);
+        message.write_u8(ClientServer::Move as u8);
+        let position = message.position;
+        message.write_u8(0);
+        message.write_u8(2);
+        for _ in 0..3 {
+            command.write(&mut message);
+        }
+        let checksum = crate::crc::generate_checksum(&message.buffer, position + 1, message.position, 5);
+        message.buffer.to_mut()[position] = (checksum & 0xff) as u8;
+        message.write_u8(ClientServer::Delta as u8);
+        message.write_u8(4);
+        message.write_u8(ClientServer::TMove as u8);
+        message.write_coordinatevector(&CoordinateVector{ x: 8.0, y: -16.0, z: 24.5 });
+        message.write_u8(ClientServer::Upload as u8);
+        message.write_i16(3_i16);
+        message.write_u8(100);
+        message.write_bytes(&[1, 2, 3]);
+
+        let bytes = message.buffer.to_vec();
+        let mut message = new_message(bytes.clone(), flags, MessageType::Connection);
+        let packet = match message.read_client_packet().unwrap() {
+            ClientPacket::Connected(packet) => packet,
+            p => panic!("expected a connected packet, got {:?}", p),
+        };
+        assert_eq!(packet.sequence, sequence);
+        assert_eq!(packet.sequence_ack, 3);
+        assert_eq!(packet.qport, 27001);
+        assert_eq!(packet.messages.len(), 6);
+        assert!(matches!(&packet.messages[0], ClientMessage::StringCommand(s) if *s.bytes == *b"new"));
+        assert_eq!(packet.messages[1], ClientMessage::Nop);
+        match &packet.messages[2] {
+            ClientMessage::Move(m) => {
+                assert_eq!(m.lossage, 2);
+                assert_eq!(m.commands[2], command);
+            },
+            m => panic!("expected move, got {:?}", m),
+        }
+        assert_eq!(packet.messages[3], ClientMessage::Delta(4));
+        assert_eq!(packet.messages[4], ClientMessage::TMove(CoordinateVector{ x: 8.0, y: -16.0, z: 24.5 }));
+        assert_eq!(packet.messages[5], ClientMessage::Upload(ClientUpload{ size: 3, percent: 100, data: vec![1, 2, 3] }));
+
+        let mut broken = bytes;
+        broken[position] ^= 0xff;
+        let mut message = new_message(broken, flags, MessageType::Connection);
+        assert!(matches!(message.read_client_packet(), Err(MessageError::ChecksumMismatch(_, _))));
+    }
+
+    #[test]
+    fn projectile_truncation() {
+        let projectile = Projectile{
+            number: None,
+            origin: CoordinateVector{ x: 3.0, y: -3.0, z: 101.9 },
+            angle: AngleVector{ x: 44.0, y: 91.0, z: 0.0 },
+        };
+        let mut message = new_message(vec![], MessageFlags::default(), MessageType::Mvd);
+        assert_eq!(projectile.write(&mut message), 6);
+        let mut message = new_message(message.buffer.to_vec(), MessageFlags::default(), MessageType::Mvd);
+        let read = Projectile::read(&mut message, false).unwrap();
+        assert_eq!(read.origin, CoordinateVector{ x: 2.0, y: -4.0, z: 100.0 });
+        assert_eq!(read.angle, AngleVector{ x: 22.5, y: 90.0, z: 0.0 });
+    }
+
+    #[test]
+    fn float_coordinates() {
+        let mut message = new_message(vec![], MessageFlags::default(), MessageType::Mvd);
+        assert_eq!(message.write_coordinate(-1000.125), 2);
+        assert_eq!(message.write_entity_coordinate(-1000.125), 2);
+
+        let flags = MessageFlags{
+            fte_protocol_extensions: FteProtocolExtensions::FLOATCOORDS,
+            ..Default::default()
+        };
+        let mut message = new_message(vec![], flags, MessageType::Connection);
+        assert_eq!(message.write_coordinate(5000.3), 4);
+        assert_eq!(message.write_entity_coordinate(5000.3), 4);
+        let mut message = new_message(message.buffer.to_vec(), flags, MessageType::Connection);
+        assert_eq!(message.read_coordinate(false).unwrap(), 5000.3);
+        assert_eq!(message.read_entity_coordinate(false).unwrap(), 5000.3);
+
+        // only entity and player coordinates are floats
+        let flags = MessageFlags{
+            mvd_protocol_extension: MvdProtocolExtensions::FLOATCOORDS,
+            ..Default::default()
+        };
+        let mut message = new_message(vec![], flags, MessageType::Mvd);
+        assert_eq!(message.write_coordinate(-1000.125), 2);
+        assert_eq!(message.write_entity_coordinate(5000.3), 4);
+        let mut message = new_message(message.buffer.to_vec(), flags, MessageType::Mvd);
+        assert_eq!(message.read_coordinate(false).unwrap(), -1000.125);
+        assert_eq!(message.read_entity_coordinate(false).unwrap(), 5000.3);
+
+        let ascii_converter = AsciiConverter::new();
+        let mut mvd_messages = messages(&ascii_converter);
+        mvd_messages.push(ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+            player_number: 4,
+            flags: DfTypes::ORIGIN | DfTypes::ORIGIN2 | DfTypes::ORIGIN3,
+            frame: 13,
+            origin: Some(CoordinateVectorOption{ x: Some(8000.5), y: Some(-12345.25), z: Some(0.1) }),
+            angle: None,
+            model: None,
+            skinnum: None,
+            effects: None,
+            weaponframe: None,
+        })));
+        let mut entity = packetentity(17);
+        entity.origin = Some(CoordinateVectorOption{ x: Some(9000.7), y: Some(-9000.3), z: None });
+        mvd_messages.push(ServerMessage::Packetentities(Packetentities{ entities: vec![entity] }));
+        round_trip(MessageType::Mvd, flags, mvd_messages);
+
+        let flags = MessageFlags{
+            protocol: ProtocolVersion::Standard as u32,
+            fte_protocol_extensions: FteProtocolExtensions::FLOATCOORDS,
+            ..Default::default()
+        };
+        round_trip(MessageType::Connection, flags, messages(&ascii_converter));
+    }
+}
@@ -0,0 +1,316 @@
+use std::borrow::Cow;
+use serde::Serialize;
+use crate::protocol::message::Message;
+use crate::protocol::message::MessageFlags;
+use crate::protocol::message::MessageType;
+use crate::protocol::types::*;
+use crate::protocol::errors::QwdParseError;
+#[cfg(feature = "state")]
+use crate::state::State;
+#[cfg(feature = "state")]
+use crate::state::errors::StateError;
+
+#[cfg(feature = "ascii_strings")]
+use crate::utils::ascii_converter::AsciiConverter;
+
+use crate::protocol::message::trace::*;
+
+/// a usercmd_t as the client recorded it, followed by the clients view angles
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
+pub struct QwdUserCommand {
+    pub command: DeltaUserCommand,
+    pub view_angles: AngleVector,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
+pub struct QwdSet {
+    pub outgoing_sequence: u32,
+    pub incoming_sequence: u32,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
+pub enum QwdData<'a> {
+    Command(QwdUserCommand),
+    Read(Packet<'a>),
+    Set(QwdSet),
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug, PartialOrd)]
+pub struct QwdFrame<'a> {
+    pub frame: u32,
+    pub time: f64,
+    pub data: QwdData<'a>,
+}
+
+impl QwdFrame<'_> {
+    /// the [`ServerMessage`]s of a recorded server packet
+    pub fn messages(&self) -> &[ServerMessage<'_>] {
+        match &self.data {
+            QwdData::Read(Packet::Connected(connected)) => &connected.messages,
+            _ => &[],
+        }
+    }
+
+    /// applies a recorded server packet with its sequence, see [`State::apply_packet`]
+#[cfg(feature = "state")]
+    pub fn apply_to(&self, state: &mut State) -> Result<(), StateError> {
+        match &self.data {
+            QwdData::Read(Packet::Connected(connected)) => state.apply_packet(connected),
+            _ => Ok(()),
+        }
+    }
+
+    /// the recorded [`DeltaUserCommand`]
+    pub fn command(&self) -> Option<&DeltaUserCommand> {
+        match &self.data {
+            QwdData::Command(command) => Some(&command.command),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Qwd<'a> {
+    pub size: usize,
+    pub finished: bool,
+    pub message: Message<'a>,
+    pub flags: MessageFlags,
+    pub frame: u32,
+    pub time: f64,
+#[cfg(feature = "trace")]
+    pub trace: bool,
+}
+
+impl<'a> Qwd<'a> {
+    pub fn new(buffer: impl Into<Cow<'a, [u8]>>,
+#[cfg(feature = "ascii_strings")]
+               maybe_ascii_converter: Option<AsciiConverter>,
+#[cfg(feature = "trace")]
+               trace: bool,
+               ) -> Result<Qwd<'a>, std::io::Error> {
+        let buffer = buffer.into();
+        let size = buffer.len();
+        let mut flags = MessageFlags::new_empty();
+        flags.protocol = ProtocolVersion::Standard as u32;
+
+        let mut message = Message::new(buffer, 0, size, false, flags,
+#[cfg(feature = "ascii_strings")]
+        maybe_ascii_converter,
+        MessageType::Connection);
+
+        #[cfg(feature = "trace")]
+        {
+            message.trace.enabled = trace;
+        }
+
+        Ok(Qwd {
+            size,
+            finished: false,
+            message,
+            flags,
+            frame: 0,
+            time: 0.0,
+#[cfg(feature = "trace")]
+            trace,
+        })
+    }
+
+    pub fn parse_frame(&mut self) -> Result<Box<QwdFrame<'a>>, QwdParseError> {
+        trace_start!(self.message, false);
+        trace_annotate!(self.message, "demo_time");
+        let time = self.message.read_f32(false)?;
+        self.time = time as f64;
+
+        trace_annotate!(self.message, "command");
+        let cmd = self.message.read_u8(false)?;
+        let data = match DemoCommand::try_from(cmd) {
+            Ok(DemoCommand::Command) => QwdData::Command(self.read_usercommand()?),
+            Ok(DemoCommand::Read) => QwdData::Read(self.read_packet()?),
+            Ok(DemoCommand::Set) => {
+                trace_annotate!(self.message, "outgoing_sequence");
+                let outgoing_sequence = self.message.read_u32(false)?;
+                trace_annotate!(self.message, "incoming_sequence");
+                let incoming_sequence = self.message.read_u32(false)?;
+                QwdData::Set(QwdSet{
+                    outgoing_sequence,
+                    incoming_sequence,
+                })
+            },
+            _ => return Err(QwdParseError::UnhandledCommand(cmd)),
+        };
+
+        let frame = Box::new(QwdFrame{
+            frame: self.frame,
+            time: self.time,
+            data,
+        });
+        self.frame += 1;
+        if self.message.position >= self.message.length {
+            self.finished = true;
+        }
+        trace_stop!(self.message);
+        Ok(frame)
+    }
+
+    fn read_usercommand(&mut self) -> Result<QwdUserCommand, QwdParseError> {
+        trace_start!(self.message, false);
+        // usercmd_t is written as is, including the padding after msec
+        trace_annotate!(self.message, "msec");
+        let msec = self.message.read_u8(false)?;
+        trace_annotate!(self.message, "padding");
+        self.message.read_bytes(3, false)?;
+        let mut angle = AngleVectorOption::default();
+        trace_annotate!(self.message, "angle.x");
+        angle.x = Some(self.message.read_f32(false)?);
+        trace_annotate!(self.message, "angle.y");
+        angle.y = Some(self.message.read_f32(false)?);
+        trace_annotate!(self.message, "angle.z");
+        angle.z = Some(self.message.read_f32(false)?);
+        trace_annotate!(self.message, "forward");
+        let forward = self.message.read_i16(false)?;
+        trace_annotate!(self.message, "side");
+        let side = self.message.read_i16(false)?;
+        trace_annotate!(self.message, "up");
+        let up = self.message.read_i16(false)?;
+        trace_annotate!(self.message, "buttons");
+        let buttons = self.message.read_u8(false)?;
+        trace_annotate!(self.message, "impulse");
+        let impulse = self.message.read_u8(false)?;
+
+        let mut view_angles = AngleVector::default();
+        trace_annotate!(self.message, "view_angles.x");
+        view_angles.x = self.message.read_f32(false)?;
+        trace_annotate!(self.message, "view_angles.y");
+        view_angles.y = self.message.read_f32(false)?;
+        trace_annotate!(self.message, "view_angles.z");
+        view_angles.z = self.message.read_f32(false)?;
+
+        let command = QwdUserCommand{
+            command: DeltaUserCommand{
+                bits: UserCommandFlags::all(),
+                angle,
+                forward: Some(forward),
+                side: Some(side),
+                up: Some(up),
+                buttons: Some(buttons),
+                impulse: Some(impulse),
+                msec: Some(msec),
+            },
+            view_angles,
+        };
+        trace_stop!(self.message);
+        Ok(command)
+    }
+
+    fn read_packet(&mut self) -> Result<Packet<'a>, QwdParseError> {
+        trace_start!(self.message, false);
+        trace_annotate!(self.message, "size");
+        let size = self.message.read_u32(false)? as usize;
+        trace_annotate!(self.message, "packet");
+        let buffer = self.message.read_bytes_cow(size as u32, false)?;
+
+        let mut message = Message::new(buffer, 0, size, false, self.flags,
+#[cfg(feature = "ascii_strings")]
+        Some(self.message.ascii_converter.clone()),
+        MessageType::Connection);
+
+        #[cfg(feature = "trace")]
+        {
+            message.trace.enabled = self.trace;
+        }
+
+        let packet = message.read_packet()?;
+        if let Packet::Connected(connected) = &packet {
+            for m in &connected.messages {
+                if let ServerMessage::Serverdata(serverdata) = m {
+                    self.flags.protocol = serverdata.protocol.clone() as u32;
+                    self.flags.fte_protocol_extensions = serverdata.fte_protocol_extension;
+                    self.flags.fte_protocol_extensions_2 = serverdata.fte_protocol_extension_2;
+                    self.flags.mvd_protocol_extension = serverdata.mvd_protocol_extension;
+                }
+            }
+        }
+        trace_stop!(self.message);
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qwd::{Qwd, QwdData};
+    use crate::protocol::types::*;
+
+    #[test]
+    fn qwd_parsing() {
+        let mut b: Vec<u8> = vec![];
+        // dem_set
+        b.extend(1.0_f32.to_le_bytes());
+        b.push(DemoCommand::Set as u8);
+        b.extend(3_u32.to_le_bytes());
+        b.extend(2_u32.to_le_bytes());
+        // dem_read with a print
+        let packet: Vec<u8> = vec![4, 0, 0, 0, 3, 0, 0, 0, 8, 2, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0];
+        b.extend(1.5_f32.to_le_bytes());
+        b.push(DemoCommand::Read as u8);
+        b.extend((packet.len() as u32).to_le_bytes());
+        b.extend(packet);
+        // dem_cmd
+        b.extend(2.0_f32.to_le_bytes());
+        b.push(DemoCommand::Command as u8);
+        b.extend([13, 0, 0, 0]);
+        b.extend(10.0_f32.to_le_bytes());
+        b.extend(90.0_f32.to_le_bytes());
+        b.extend(0.0_f32.to_le_bytes());
+        b.extend(400_i16.to_le_bytes());
+        b.extend((-200_i16).to_le_bytes());
+        b.extend(0_i16.to_le_bytes());
+        // buttons, impulse
+        b.extend([1, 7]);
+        b.extend(10.0_f32.to_le_bytes());
+        b.extend(90.0_f32.to_le_bytes());
+        b.extend(0.0_f32.to_le_bytes());
+
+        let mut qwd = Qwd::new(b,
+#[cfg(feature = "ascii_strings")]
+            None,
+#[cfg(feature = "trace")]
+            false,
+            ).unwrap();
+        let mut frames = vec![];
+        while !qwd.finished {
+            frames.push(qwd.parse_frame().unwrap());
+        }
+        assert_eq!(frames.len(), 3);
+        match &frames[0].data {
+            QwdData::Set(set) => {
+                assert_eq!(set.outgoing_sequence, 3);
+                assert_eq!(set.incoming_sequence, 2);
+            },
+            _ => panic!("its not set!"),
+        }
+        assert_eq!(frames[1].time, 1.5);
+        match &frames[1].messages()[0] {
+            ServerMessage::Print(p) => {
+                assert_eq!(p.from, 2);
+                assert_eq!(p.message.bytes.as_ref(), b"hello");
+            },
+            _ => panic!("its not print!"),
+        }
+        let command = frames[2].command().unwrap();
+        assert_eq!(command.msec, Some(13));
+        assert_eq!(command.angle.y, Some(90.0));
+        assert_eq!(command.forward, Some(400));
+        assert_eq!(command.side, Some(-200));
+        assert_eq!(command.buttons, Some(1));
+        assert_eq!(command.impulse, Some(7));
+
+#[cfg(feature = "state")]
+        {
+            let mut state = crate::state::State::new();
+            for frame in &frames {
+                frame.apply_to(&mut state).unwrap();
+            }
+            assert_eq!(state.entity_sequence, 4);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use serde::Serialize;
+use crate::protocol::types::{AngleVector, CoordinateVector, StringByte};
+use crate::state::{Entity, Player, State};
+use crate::utils::userinfo::Userinfo;
+
+/// the fields of a [`Player`] that changed between two states
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PlayerDiff {
+    pub player_number: u16,
+    pub frags: Option<i16>,
+    pub ping: Option<u16>,
+    pub pl: Option<u8>,
+    pub name: Option<StringByte<'static>>,
+    pub origin: Option<CoordinateVector>,
+    pub angle: Option<AngleVector>,
+    /// userinfo keys that were added or changed
+    pub userinfo: Vec<(StringByte<'static>, StringByte<'static>)>,
+    /// userinfo keys that were removed
+    pub userinfo_removed: Vec<StringByte<'static>>,
+    /// (index, value) of the stats that changed
+    pub stats: Vec<(u8, i32)>,
+}
+
+impl PlayerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.frags.is_none() && self.ping.is_none() && self.pl.is_none() &&
+            self.name.is_none() && self.origin.is_none() && self.angle.is_none() &&
+            self.userinfo.is_empty() && self.userinfo_removed.is_empty() && self.stats.is_empty()
+    }
+
+    fn apply(&self, player: &mut Player) {
+        if let Some(frags) = self.frags {
+            player.frags = frags;
+        }
+        if let Some(ping) = self.ping {
+            player.ping = ping;
+        }
+        if let Some(pl) = self.pl {
+            player.pl = pl;
+        }
+        if let Some(origin) = self.origin {
+            player.origin = origin;
+        }
+        if let Some(angle) = self.angle {
+            player.angle = angle;
+        }
+        for key in &self.userinfo_removed {
+            player.userinfo.remove_key(key);
+        }
+        for (key, value) in &self.userinfo {
+            player.userinfo.update_key_value(key, value);
+        }
+        if !self.userinfo.is_empty() {
+            player.update_userinfo();
+        }
+        if let Some(name) = &self.name {
+            player.name = name.clone();
+        }
+        for (stat, value) in &self.stats {
+            if let Some(stat) = player.stats.get_mut(*stat as usize) {
+                *stat = *value;
+            }
+        }
+    }
+}
+
+/// what changed from one [`State`] to another, see [`State::diff`]
+///
+/// everything is sorted by player number or entity index
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct StateDiff {
+    pub players_joined: Vec<(u16, Player)>,
+    pub players_left: Vec<u16>,
+    pub players_changed: Vec<PlayerDiff>,
+    pub entities_added: Vec<Entity>,
+    pub entities_removed: Vec<u16>,
+    /// entities that moved or changed otherwise
+    pub entities_changed: Vec<Entity>,
+    /// serverinfo keys that were added or changed
+    pub serverinfo: Vec<(StringByte<'static>, StringByte<'static>)>,
+    /// serverinfo keys that were removed
+    pub serverinfo_removed: Vec<StringByte<'static>>,
+}
+
+/// keys of `to` that are missing or have a different value in `from`
+fn userinfo_diff(from: &Userinfo, to: &Userinfo) -> Vec<(StringByte<'static>, StringByte<'static>)> {
+    to.values.iter()
+        .filter(|(key, value)| !from.values.iter().any(|(k, v)| k.bytes == key.bytes && v.bytes == value.bytes))
+        .cloned()
+        .collect()
+}
+
+/// keys of `from` that are missing in `to`
+fn userinfo_removed(from: &Userinfo, to: &Userinfo) -> Vec<StringByte<'static>> {
+    from.values.iter()
+        .filter(|(key, _)| !to.values.iter().any(|(k, _)| k.bytes == key.bytes))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+fn player_diff(player_number: u16, from: &Player, to: &Player) -> PlayerDiff {
+    PlayerDiff {
+        player_number,
+        frags: (from.frags != to.frags).then_some(to.frags),
+        ping: (from.ping != to.ping).then_some(to.ping),
+        pl: (from.pl != to.pl).then_some(to.pl),
+        name: (from.name.bytes != to.name.bytes).then(|| to.name.clone()),
+        origin: (from.origin != to.origin).then_some(to.origin),
+        angle: (from.angle != to.angle).then_some(to.angle),
+        userinfo: userinfo_diff(&from.userinfo, &to.userinfo),
+        userinfo_removed: userinfo_removed(&from.userinfo, &to.userinfo),
+        stats: from.stats.iter().zip(to.stats.iter())
+            .enumerate()
+            .filter(|(_, (from, to))| from != to)
+            .map(|(stat, (_, to))| (stat as u8, *to))
+            .collect(),
+    }
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.players_joined.is_empty() && self.players_left.is_empty() && self.players_changed.is_empty() &&
+            self.entities_added.is_empty() && self.entities_removed.is_empty() && self.entities_changed.is_empty() &&
+            self.serverinfo.is_empty() && self.serverinfo_removed.is_empty()
+    }
+
+    /// applies the changes to `state`, fields that aren't part of the diff are left alone
+    pub fn apply(&self, state: &mut State) {
+        for player_number in &self.players_left {
+            state.players.remove(player_number);
+        }
+        for (player_number, player) in &self.players_joined {
+            state.players.insert(*player_number, player.clone());
+        }
+        for player_diff in &self.players_changed {
+            if let Some(player) = state.players.get_mut(&player_diff.player_number) {
+                player_diff.apply(player);
+            }
+        }
+        for index in &self.entities_removed {
+            state.entities.remove(index);
+        }
+        for entity in self.entities_added.iter().chain(self.entities_changed.iter()) {
+            state.entities.insert(entity.index, *entity);
+        }
+        for key in &self.serverinfo_removed {
+            state.serverinfo.remove_key(key);
+        }
+        for (key, value) in &self.serverinfo {
+            state.serverinfo.update_key_value(key, value);
+        }
+    }
+}
+
+impl State {
+    /// the changes that turn `self` into `other`
+    pub fn diff(&self, other: &State) -> StateDiff {
+        let mut diff = StateDiff::default();
+        for (player_number, player) in &other.players {
+            match self.players.get(player_number) {
+                None => diff.players_joined.push((*player_number, player.clone())),
+                Some(from) => {
+                    let player_diff = player_diff(*player_number, from, player);
+                    if !player_diff.is_empty() {
+                        diff.players_changed.push(player_diff);
+                    }
+                },
+            }
+        }
+        diff.players_left = self.players.keys()
+            .filter(|player_number| !other.players.contains_key(player_number))
+            .copied()
+            .collect();
+
+        for (index, entity) in &other.entities {
+            match self.entities.get(index) {
+                None => diff.entities_added.push(*entity),
+                Some(from) if from != entity => diff.entities_changed.push(*entity),
+                Some(_) => {},
+            }
+        }
+        diff.entities_removed = self.entities.keys()
+            .filter(|index| !other.entities.contains_key(index))
+            .copied()
+            .collect();
+        diff.serverinfo = userinfo_diff(&self.serverinfo.info, &other.serverinfo.info);
+        diff.serverinfo_removed = userinfo_removed(&self.serverinfo.info, &other.serverinfo.info);
+
+        diff.players_joined.sort_by_key(|(player_number, _)| *player_number);
+        diff.players_left.sort();
+        diff.players_changed.sort_by_key(|player| player.player_number);
+        diff.entities_added.sort_by_key(|entity| entity.index);
+        diff.entities_removed.sort();
+        diff.entities_changed.sort_by_key(|entity| entity.index);
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::MvdTarget;
+    use crate::protocol::types::*;
+    use crate::state::State;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    #[test]
+    fn diff_and_apply() {
+        let ascii_converter = AsciiConverter::new();
+        let target = MvdTarget{ to: 1, command: DemoCommand::Single };
+        let mut from = State::new();
+        from.apply_messages_mvd(&vec![
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 1, uid: 1, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice\\team\\red") }),
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 2, uid: 2, userinfo: ascii_converter.convert_to_stringbyte("\\name\\bob\\team\\blue") }),
+            ServerMessage::Packetentities(Packetentities{ entities: vec![
+                Packetentity{ entity_index: 10, ..Default::default() },
+                Packetentity{ entity_index: 11, ..Default::default() },
+            ]}),
+        ], target.clone()).unwrap();
+
+        let mut to = from.clone();
+        to.apply_messages_mvd(&vec![
+            ServerMessage::Updatefrags(Updatefrags{ player_number: 1, frags: 3 }),
+            ServerMessage::Updatestat(Updatestat{ stat: 0, value: 75 }),
+            ServerMessage::Setinfo(Setinfo{ player_number: 1, key: ascii_converter.convert_to_stringbyte("team"), value: ascii_converter.convert_to_stringbyte("blue") }),
+            ServerMessage::Updateping(Updateping{ player_number: 3, ping: 25 }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("status"), value: ascii_converter.convert_to_stringbyte("3 min left") }),
+            ServerMessage::Deltapacketentities(Deltapacketentities{ from: 0, entities: vec![
+                Packetentity{ entity_index: 10, origin: Some(CoordinateVectorOption{ x: Some(64.0), y: None, z: None }), ..Default::default() },
+                Packetentity{ entity_index: 11, remove: true, ..Default::default() },
+                Packetentity{ entity_index: 12, ..Default::default() },
+            ]}),
+        ], target).unwrap();
+        to.players.remove(&2);
+
+        let diff = from.diff(&to);
+        assert_eq!(diff.players_joined.len(), 1);
+        assert_eq!(diff.players_left, vec![2]);
+        assert_eq!(diff.players_changed.len(), 1);
+        let player = &diff.players_changed[0];
+        assert_eq!(player.frags, Some(3));
+        assert_eq!(player.ping, None);
+        assert_eq!(player.stats, vec![(0, 75)]);
+        assert_eq!(player.userinfo.len(), 1);
+        assert_eq!(diff.entities_added.iter().map(|e| e.index).collect::<Vec<_>>(), vec![12]);
+        assert_eq!(diff.entities_removed, vec![11]);
+        assert_eq!(diff.entities_changed[0].origin.x, 64.0);
+        assert_eq!(diff.serverinfo.len(), 1);
+
+        let mut state = from.clone();
+        diff.apply(&mut state);
+        assert!(state.diff(&to).is_empty());
+        assert_eq!(state.players[&1].team.bytes.as_ref(), b"blue");
+        assert!(to.diff(&to).is_empty());
+    }
+
+    fn players(ascii_converter: &AsciiConverter) -> State {
+        let mut state = State::new();
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Updateuserinfo(Updateuserinfo{ player_number: 1, uid: 1, userinfo: ascii_converter.convert_to_stringbyte("\\name\\alice\\team\\red\\skin\\base") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("map"), value: ascii_converter.convert_to_stringbyte("dm2") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("status"), value: ascii_converter.convert_to_stringbyte("Standby") }),
+        ], MvdTarget{ to: 1, command: DemoCommand::Single }).unwrap();
+        state
+    }
+
+    #[test]
+    fn moved_player_round_trip() {
+        let ascii_converter = AsciiConverter::new();
+        let from = players(&ascii_converter);
+        let mut to = from.clone();
+        to.apply_messages_mvd(&vec![
+            ServerMessage::Setinfo(Setinfo{ player_number: 1, key: ascii_converter.convert_to_stringbyte("name"), value: ascii_converter.convert_to_stringbyte("bob") }),
+        ], MvdTarget{ to: 1, command: DemoCommand::Single }).unwrap();
+        let player = to.players.get_mut(&1).unwrap();
+        player.origin = CoordinateVector{ x: 128.0, y: -64.0, z: 24.0 };
+        player.angle = AngleVector{ x: 10.0, y: 90.0, z: 0.0 };
+
+        let diff = from.diff(&to);
+        let player = &diff.players_changed[0];
+        assert_eq!(player.origin, Some(CoordinateVector{ x: 128.0, y: -64.0, z: 24.0 }));
+        assert_eq!(player.angle, Some(AngleVector{ x: 10.0, y: 90.0, z: 0.0 }));
+        assert_eq!(player.name.as_ref().unwrap().bytes.as_ref(), b"bob");
+
+        let mut state = from.clone();
+        diff.apply(&mut state);
+        assert!(state.diff(&to).is_empty());
+        assert_eq!(state.players[&1].origin, to.players[&1].origin);
+        assert_eq!(state.players[&1].angle, to.players[&1].angle);
+        assert_eq!(state.players[&1].name.bytes.as_ref(), b"bob");
+    }
+
+    #[test]
+    fn removed_key_round_trip() {
+        let ascii_converter = AsciiConverter::new();
+        let from = players(&ascii_converter);
+        let mut to = from.clone();
+        to.players.get_mut(&1).unwrap().userinfo.remove_key(&ascii_converter.convert_to_stringbyte("skin"));
+        to.serverinfo.remove_key(&ascii_converter.convert_to_stringbyte("status"));
+
+        let diff = from.diff(&to);
+        assert_eq!(diff.players_changed[0].userinfo_removed.len(), 1);
+        assert_eq!(diff.serverinfo_removed.len(), 1);
+        assert!(diff.serverinfo.is_empty());
+
+        let mut state = from.clone();
+        diff.apply(&mut state);
+        assert!(state.diff(&to).is_empty());
+        let keys = |state: &State| state.players[&1].userinfo.values.iter().map(|(k, _)| k.bytes.to_vec()).collect::<Vec<_>>();
+        assert_eq!(keys(&state), keys(&to));
+        assert_eq!(state.serverinfo.get("status"), None);
+        assert!(state.serverinfo.status.bytes.is_empty());
+        assert_eq!(state.serverinfo.map.bytes.as_ref(), b"dm2");
+    }
+}
@@ -0,0 +1,14 @@
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum StateError {
+    #[error("unhandled message {0}")]
+    UnhandledMessage(String),
+    #[error("{0} is not applicable to a player")]
+    NotAPlayerMessage(String),
+    #[error("stat {0} is out of range")]
+    StatOutOfRange(u8),
+    #[error("entity frame {0} to delta from is missing")]
+    MissingDeltaFrame(u32),
+}
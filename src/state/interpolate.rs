@@ -0,0 +1,81 @@
+use crate::protocol::types::{AngleVector, CoordinateVector};
+use crate::state::State;
+
+/// anything moving further than this between two states teleported
+pub const MAX_INTERPOLATE_DISTANCE: f32 = 200.0;
+
+fn lerp(from: (&CoordinateVector, &AngleVector), to: (&CoordinateVector, &AngleVector), t: f32) -> Option<(CoordinateVector, AngleVector)> {
+    if from.0.distance(to.0) > MAX_INTERPOLATE_DISTANCE {
+        return None;
+    }
+    Some((from.0.lerp(to.0, t), from.1.lerp(to.1, t)))
+}
+
+impl State {
+    /// the state at `t` between `a` (0.0) and `b` (1.0)
+    ///
+    /// for frames at `a_time` and `b_time` from [`crate::mvd::MvdFrame::time`],
+    /// `t` is `(time - a_time) / (b_time - a_time)`. Only origins and angles of players and
+    /// entities that are in both states are interpolated, everything else is taken from `b`.
+    /// Players and entities that teleported are not interpolated either
+    pub fn interpolate(a: &State, b: &State, t: f64) -> State {
+        let t = t.clamp(0.0, 1.0) as f32;
+        let mut state = b.clone();
+        for (player_number, player) in state.players.iter_mut() {
+            let from = match a.players.get(player_number) {
+                Some(from) => from,
+                None => continue,
+            };
+            if from.teleports != player.teleports {
+                continue;
+            }
+            if let Some((origin, angle)) = lerp((&from.origin, &from.angle), (&player.origin, &player.angle), t) {
+                player.origin = origin;
+                player.angle = angle;
+            }
+        }
+        for (index, entity) in state.entities.iter_mut() {
+            let from = match a.entities.get(index) {
+                Some(from) if from.model == entity.model => from,
+                _ => continue,
+            };
+            if let Some((origin, angle)) = lerp((&from.origin, &from.angle), (&entity.origin, &entity.angle), t) {
+                entity.origin = origin;
+                entity.angle = angle;
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::types::*;
+    use crate::state::{Entity, State};
+
+    #[test]
+    fn interpolate() {
+        let mut a = State::new();
+        a.players.entry(1).or_default().origin = CoordinateVector{ x: 0.0, y: 0.0, z: 0.0 };
+        a.players.entry(1).or_default().angle = AngleVector{ x: 0.0, y: 350.0, z: 0.0 };
+        a.players.entry(2).or_default().origin = CoordinateVector{ x: 0.0, y: 0.0, z: 0.0 };
+        a.entities.insert(5, Entity{ index: 5, origin: CoordinateVector{ x: 0.0, y: 0.0, z: 0.0 }, ..Default::default() });
+        let mut b = a.clone();
+        b.players.get_mut(&1).unwrap().origin = CoordinateVector{ x: 100.0, y: 0.0, z: 0.0 };
+        b.players.get_mut(&1).unwrap().angle = AngleVector{ x: 0.0, y: 10.0, z: 0.0 };
+        // a teleport
+        b.players.get_mut(&2).unwrap().origin = CoordinateVector{ x: 1000.0, y: 0.0, z: 0.0 };
+        b.entities.get_mut(&5).unwrap().origin = CoordinateVector{ x: 0.0, y: 0.0, z: 50.0 };
+
+        let state = State::interpolate(&a, &b, 0.5);
+        assert_eq!(state.players[&1].origin.x, 50.0);
+        assert_eq!(state.players[&1].angle.y.rem_euclid(360.0), 0.0);
+        assert_eq!(state.players[&2].origin.x, 1000.0);
+        assert_eq!(state.entities[&5].origin.z, 25.0);
+
+        // teleports signaled with setangle are not interpolated, however short
+        b.players.get_mut(&1).unwrap().teleports = 1;
+        let state = State::interpolate(&a, &b, 0.25);
+        assert_eq!(state.players[&1].origin.x, 100.0);
+    }
+}
@@ -6,9 +6,31 @@ use crate::utils::ascii_converter::AsciiConverter;
 use crate::mvd::MvdTarget;
 use std::collections::HashMap;
 
+pub mod diff;
+pub mod errors;
+pub mod interpolate;
+pub mod serverinfo;
+pub mod stats;
+use errors::StateError;
+pub use diff::StateDiff;
+pub use serverinfo::ServerInfo;
+pub use stats::{Items, StatChange, StatType};
+
 
 pub type Stat = [i32;32];
 
+/// number of entity frames that are kept to delta from
+pub const UPDATE_BACKUP: usize = 64;
+pub const UPDATE_MASK: u32 = UPDATE_BACKUP as u32 - 1;
+
+/// the entities of one packet, sorted by index
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EntityFrame {
+    pub sequence: u32,
+    pub valid: bool,
+    pub entities: Vec<Entity>,
+}
+
 #[derive(Serialize, Clone, Debug, Default)]
 pub struct Player {
     pub frags: i16,
@@ -17,18 +39,33 @@ pub struct Player {
     pub entertime: f32,
     pub uid: u32,
     pub userinfo: Userinfo,
-    pub name: StringByte,
-    pub team: StringByte,
+    pub name: StringByte<'static>,
+    pub team: StringByte<'static>,
     pub spectator: bool,
-    pub top_color: StringByte,
-    pub bottom_color: StringByte,
+    pub top_color: StringByte<'static>,
+    pub bottom_color: StringByte<'static>,
     pub origin: CoordinateVector,
     pub angle: AngleVector,
     pub model: u8,
     pub skinnum: u8,
     pub effects: u8,
     pub weaponframe: u8,
+    pub frame: u8,
+    pub velocity: VelocityVector,
+    pub dead: bool,
+    pub gib: bool,
+    /// only set from [`PlayerinfoConnection`]
+    pub msec: u8,
+    /// counts the [`ServerMessage::Setangle`] sent on teleports, only with
+    /// [`MvdProtocolExtensions::HIGHLAGTELEPORT`]
+    pub teleports: u32,
+    /// the last usercmd, only set from [`PlayerinfoConnection`]
+    pub command: Option<DeltaUserCommand>,
     pub stats: Stat,
+    /// only set from [`ServerMessage::Maxspeed`]
+    pub maxspeed: Option<f32>,
+    /// only set from [`ServerMessage::Entgravity`]
+    pub entgravity: Option<f32>,
 }
 
 impl Player {
@@ -51,7 +88,7 @@ impl Player {
     }
 }
 
-#[derive(Serialize, Copy, Clone, Debug, Default)]
+#[derive(Serialize, Copy, Clone, PartialEq, Debug, Default)]
 pub struct Entity {
     pub index: u16,
     pub model: u16,
@@ -76,15 +113,15 @@ impl Entity {
         }
         if delta.colormap.is_some() {
             let v = delta.colormap.unwrap();
-            self.frame= v;
+            self.colormap = v;
         }
         if delta.skin.is_some() {
             let v = delta.skin.unwrap();
-            self.frame= v;
+            self.skinnum = v;
         }
         if delta.effects.is_some() {
             let v = delta.effects.unwrap();
-            self.frame= v;
+            self.effects = v;
         }
         if delta.origin.is_some() {
             let v = delta.origin.unwrap();
@@ -100,7 +137,8 @@ impl Entity {
     /// create [`Entity`] from [`ServerMessage::Spawnbaseline`]
     pub fn from_baseline(baseline: &Spawnbaseline) -> Entity {
         Entity {
-            index: baseline.model_index as u16,
+            index: baseline.index,
+            model: baseline.model_index as u16,
             frame: baseline.model_frame,
             colormap: baseline.colormap,
             skinnum: baseline.skinnum,
@@ -113,7 +151,7 @@ impl Entity {
     /// create [`Entity`] from [`ServerMessage::Spawnstatic`]
     pub fn from_static(static_ent: &Spawnstatic) -> Entity {
         Entity {
-            index: static_ent.model_index as u16,
+            model: static_ent.model_index as u16,
             frame: static_ent.model_frame,
             colormap: static_ent.colormap,
             skinnum: static_ent.skinnum,
@@ -169,15 +207,33 @@ impl Sound {
 pub struct State {
 #[cfg(feature = "ascii_strings")]
     ascii_converter: AsciiConverter,
-    pub serverdata: Serverdata,
+    pub serverdata: Serverdata<'static>,
     pub players: HashMap<u16, Player>,
-    pub sounds: Vec<StringByte>,
-    pub models: Vec<StringByte>,
+    pub sounds: Vec<StringByte<'static>>,
+    pub models: Vec<StringByte<'static>>,
     pub baseline_entities: HashMap<u16, Entity>,
     pub static_entities: Vec<Spawnstatic>,
     pub entities: HashMap<u16, Entity>,
+    /// the last [`UPDATE_BACKUP`] entity frames, indexed by sequence & [`UPDATE_MASK`]
+    pub entity_frames: Vec<EntityFrame>,
+    /// sequence of the next entity frame, set with [`State::set_incoming_sequence`] when not
+    /// reading a mvd
+    pub entity_sequence: u32,
     pub temp_entities: HashMap<u16, Tempentity>,
+    /// nails visible in the last frame
+    pub projectiles: Vec<Projectile>,
+    pub paused: bool,
+    pub killed_monsters: u32,
+    pub found_secrets: u32,
     pub static_sounds: Vec<Spawnstaticsound>,
+    pub lightstyles: HashMap<u8, StringByte<'static>>,
+    pub cdtrack: u8,
+    pub serverinfo: ServerInfo,
+    /// stats that changed while applying the last messages
+    pub stat_changes: Vec<StatChange>,
+    /// errors are recorded in `warnings` instead of being returned
+    pub lenient: bool,
+    pub warnings: Vec<StateError>,
 }
 
 impl State {
@@ -195,7 +251,22 @@ impl State {
         }
     }
 
-    fn update_player(&mut self, player_index: u16, message: &ServerMessage) {
+    /// model index of progs/player.mdl, connection playerinfos only send the model if it differs
+    fn player_model(&self) -> u8 {
+        self.models.iter()
+            .position(|model| *model.bytes == *b"progs/player.mdl")
+            .map_or(0, |index| index as u8 + 1)
+    }
+
+    /// the player the connection belongs to, stats sent over it are its own
+    fn own_player_number(&self) -> u16 {
+        // spectators have the high bit set
+        (self.serverdata.player_number & 0x7f) as u16
+    }
+
+    fn update_player(&mut self, player_index: u16, message: &ServerMessage) -> Result<(), StateError> {
+        let player_model = self.player_model();
+        let highlag_teleport = self.serverdata.mvd_protocol_extension.contains(MvdProtocolExtensions::HIGHLAGTELEPORT);
         let p = self.players.get_mut(&player_index);
         let player = match p {
             Some(player) =>  player,
@@ -229,26 +300,72 @@ impl State {
                 player.userinfo.update(&data.userinfo);
                 player.update_userinfo();
             }
-            ServerMessage::Playerinfo(_data) => {
-                //panic!("FIXME");
-                /*
-                if data.origin.is_some() {
-                    data.origin.unwrap().apply_to(&mut player.origin);
+            ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(data)) => {
+                // only what changed since the last frame is sent
+                if let Some(origin) = &data.origin {
+                    origin.apply_to(&mut player.origin);
+                }
+                if let Some(angle) = &data.angle {
+                    angle.apply_to(&mut player.angle);
+                }
+                player.frame = data.frame;
+                if let Some(model) = data.model {
+                    player.model = model;
+                }
+                if let Some(skinnum) = data.skinnum {
+                    player.skinnum = skinnum;
                 }
-                if data.angle.is_some() {
-                    data.angle.unwrap().apply_to(&mut player.angle);
+                if let Some(effects) = data.effects {
+                    player.effects = effects;
                 }
-                player.model = data.model.unwrap_or(0);
+                if let Some(weaponframe) = data.weaponframe {
+                    player.weaponframe = weaponframe;
+                }
+                player.dead = data.flags.contains(DfTypes::DEAD);
+                player.gib = data.flags.contains(DfTypes::GIB);
+            }
+            ServerMessage::Playerinfo(Playerinfo::PlayerinfoConnectionT(data)) => {
+                // everything is sent, missing values are the defaults
+                player.origin = data.origin;
+                player.frame = data.frame;
+                player.msec = data.msec.unwrap_or(0);
+                if let Some(command) = &data.command {
+                    player.angle = AngleVector{
+                        x: command.angle.x.unwrap_or(0.0),
+                        y: command.angle.y.unwrap_or(0.0),
+                        z: command.angle.z.unwrap_or(0.0),
+                    };
+                    player.command = Some(command.clone());
+                }
+                player.velocity = VelocityVector{
+                    x: data.velocity.x.unwrap_or(0),
+                    y: data.velocity.y.unwrap_or(0),
+                    z: data.velocity.z.unwrap_or(0),
+                };
+                player.model = data.model.unwrap_or(player_model);
                 player.skinnum = data.skinnum.unwrap_or(0);
                 player.effects = data.effects.unwrap_or(0);
                 player.weaponframe = data.weaponframe.unwrap_or(0);
-                */
+                player.dead = data.flags.contains(PFTypes::DEAD);
+                player.gib = data.flags.contains(PFTypes::GIB);
             }
             ServerMessage::Updatestatlong(data) => {
-                player.stats[data.stat as usize] = data.value;
+                let from = match player.stats.get_mut(data.stat as usize) {
+                    Some(stat) => std::mem::replace(stat, data.value),
+                    None => return Err(StateError::StatOutOfRange(data.stat)),
+                };
+                if from != data.value {
+                    self.stat_changes.push(StatChange{ player_number: player_index, stat: data.stat, from, to: data.value });
+                }
             }
             ServerMessage::Updatestat(data) => {
-                player.stats[data.stat as usize] = data.value as i32;
+                let from = match player.stats.get_mut(data.stat as usize) {
+                    Some(stat) => std::mem::replace(stat, data.value as i32),
+                    None => return Err(StateError::StatOutOfRange(data.stat)),
+                };
+                if from != data.value as i32 {
+                    self.stat_changes.push(StatChange{ player_number: player_index, stat: data.stat, from, to: data.value as i32 });
+                }
             }
             ServerMessage::Setinfo(data) => {
                 player.userinfo.update_key_value(&data.key, &data.value);
@@ -256,255 +373,630 @@ impl State {
             }
             ServerMessage::Setangle(data) => {
                 player.angle = data.angle;
+                if highlag_teleport {
+                    player.teleports = player.teleports.wrapping_add(1);
+                }
+            }
+            ServerMessage::Maxspeed(data) => {
+                player.maxspeed = Some(data.maxspeed);
             }
-            _ => { panic!("{:?}, is not applicable to player", message)}
+            ServerMessage::Entgravity(data) => {
+                player.entgravity = Some(data.gravity);
+            }
+            _ => return Err(StateError::NotAPlayerMessage(message.to_string())),
         }
+        Ok(())
     }
 
-    fn packet_entities(&mut self, packet_entities: &Packetentities) {
-        for packet_entity in &packet_entities.entities {
-            self.baseline_entities.insert(packet_entity.entity_index, Entity::from_packetentity(packet_entity));
+    fn handle_error(&mut self, result: Result<(), StateError>) -> Result<(), StateError> {
+        match result {
+            Err(e) if self.lenient => {
+                self.warnings.push(e);
+                Ok(())
+            },
+            result => result,
         }
     }
 
-    fn deltapacket_entities(&mut self, deltapacket_entities: &Deltapacketentities) {
-        for deltapacket_entity in &deltapacket_entities.entities {
-            if deltapacket_entity.remove {
-                self.entities.remove(&deltapacket_entity.entity_index);
-            } else {
-                let e = self.entities.get_mut(&deltapacket_entity.entity_index);
-                if let Some(value) = e {
-                    value.apply_delta(deltapacket_entity);
-                } else {
-                    // @TODO: FIXME
-                    //println!("{:?}", deltapacket_entity);
-                    //panic!("deltapacket implementation");
-                }
+    /// the sequence of the packet that is applied next, deltas reference frames by it
+    pub fn set_incoming_sequence(&mut self, sequence: u32) {
+        self.entity_sequence = sequence;
+    }
+
+    fn entity_frame(&self, sequence: u32) -> Option<&EntityFrame> {
+        let frame = self.entity_frames.get((sequence & UPDATE_MASK) as usize)?;
+        if !frame.valid || frame.sequence != sequence {
+            return None;
+        }
+        Some(frame)
+    }
+
+    fn baseline_entity(&self, index: u16) -> Entity {
+        match self.baseline_entities.get(&index) {
+            Some(baseline) => Entity{ index, ..*baseline },
+            None => Entity{ index, ..Default::default() },
+        }
+    }
+
+    /// merges the deltas into the `from` frame, entities not in it start from their baseline
+    fn update_entities(&mut self, from: Option<&[Entity]>, deltas: &[Packetentity]) {
+        let mut deltas: Vec<&Packetentity> = deltas.iter().collect();
+        deltas.sort_by_key(|d| d.entity_index);
+        let mut entities = vec![];
+        let mut old = from.unwrap_or(&[]).iter().peekable();
+        for delta in deltas {
+            while let Some(entity) = old.next_if(|e| e.index < delta.entity_index) {
+                entities.push(*entity);
             }
-            //self.baseline_entities.insert(deltapacket_entity.entity_index, Entity::from_packetentity(&deltapacket_entity));
+            let mut entity = match old.next_if(|e| e.index == delta.entity_index) {
+                Some(entity) => *entity,
+                None => self.baseline_entity(delta.entity_index),
+            };
+            if delta.remove {
+                continue;
+            }
+            entity.apply_delta(delta);
+            entities.push(entity);
+        }
+        entities.extend(old);
+
+        if self.entity_frames.len() != UPDATE_BACKUP {
+            self.entity_frames = vec![EntityFrame::default(); UPDATE_BACKUP];
         }
+        self.entities = entities.iter().map(|e| (e.index, *e)).collect();
+        self.entity_frames[(self.entity_sequence & UPDATE_MASK) as usize] = EntityFrame {
+            sequence: self.entity_sequence,
+            valid: true,
+            entities,
+        };
+    }
+
+    fn packet_entities(&mut self, packet_entities: &Packetentities) {
+        // nails are only sent after the entities if there are any
+        self.projectiles.clear();
+        self.update_entities(None, &packet_entities.entities);
+    }
+
+    /// `from` is the sequence the delta is based on
+    fn deltapacket_entities(&mut self, deltapacket_entities: &Deltapacketentities, from: u32) -> Result<(), StateError> {
+        self.projectiles.clear();
+        let old = match self.entity_frame(from) {
+            Some(frame) => frame.entities.clone(),
+            // the frame is too old or was never received
+            None => return Err(StateError::MissingDeltaFrame(from)),
+        };
+        self.update_entities(Some(&old), &deltapacket_entities.entities);
+        Ok(())
     }
 
     fn temp_entities(&mut self, temp_entity: &Tempentity) {
         self.temp_entities.insert(temp_entity.entity,  temp_entity.clone());
     }
 
-    pub fn apply_messages_mvd(&mut self, messages: &'_ Vec<ServerMessage>, last: MvdTarget) {
+    pub fn apply_messages_mvd(&mut self, messages: &'_ Vec<ServerMessage>, last: MvdTarget) -> Result<(), StateError> {
+        self.stat_changes.clear();
         for message in messages {
-            match message {
-                ServerMessage::Serverdata(data) => {
-                    self.serverdata = data.clone();
-                },
-                ServerMessage::Soundlist(data) => {
-                    self.sounds.extend(data.sounds.clone());
-                }
-                ServerMessage::Modellist(data) => {
-                    self.models.extend(data.models.clone());
-                }
-                ServerMessage::Spawnbaseline(data) => {
-                    self.baseline_entities.insert(data.index, Entity::from_baseline(data));
-                }
-                ServerMessage::Spawnstatic(data) => {
-                    self.static_entities.push(*data)
-                }
-                ServerMessage::Cdtrack(_) => {
-                    continue
-                }
-                ServerMessage::Stufftext(_) => {
-                    continue
-                },
-                ServerMessage::Spawnstaticsound(data) => {
-                    self.static_sounds.push(data.clone());
-                }
-                ServerMessage::Updatefrags(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateping(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updatepl(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateentertime(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateuserinfo(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Playerinfo(_data) => {
-                    //panic!("FIXME");
-                    /*
-                    self.update_player(data.player_number as u16, message);
-                    */
-                }
-                ServerMessage::Updatestatlong(_) => {
-                    self.update_player(last.to as u16, message);
-                }
-                ServerMessage::Updatestat(_) => {
-                    self.update_player(last.to as u16, message);
-                }
-                ServerMessage::Setinfo(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Lightstyle(_) => {
-                    // ignore
-                }
-                ServerMessage::Serverinfo(_) => {
-                    // ignore, but probably shouldnt be
-                }
-                ServerMessage::Centerprint(_) => {
-                    // ignore
-                }
-                ServerMessage::Packetentities(data) => {
-                    self.packet_entities(data);
-                }
-                ServerMessage::Deltapacketentities(data) => {
-                    self.deltapacket_entities(data);
-                }
-                ServerMessage::Tempentity(data) => {
-                    self.temp_entities(data);
-                }
-                ServerMessage::Print(_) => {
-                    // ignore
-                }
-                ServerMessage::Sound(_) => {
-                    // maybe keep the sounds?
-                }
-                ServerMessage::Damage(_) => {
-                    // ignore
-                }
-                ServerMessage::Setangle(data) => {
-                    self.update_player(data.index as u16, message);
-                }
-                ServerMessage::Smallkick(_) => {
-                    // ignore
-                }
-                ServerMessage::Muzzleflash(_) => {
-                    // ignore
-                }
-                ServerMessage::Chokecount(_) => {
-                    // ignore
-                }
-                ServerMessage::Bigkick(_) => {
-                    // ignore
-                }
-                ServerMessage::Intermission(_) => {
-                    // ignore
-                }
-                ServerMessage::Disconnect(_) => {
-                    // ignore
+            let result = self.apply_message_mvd(message, &last);
+            self.handle_error(result)?;
+        }
+        Ok(())
+    }
+
+    fn apply_message_mvd(&mut self, message: &ServerMessage, last: &MvdTarget) -> Result<(), StateError> {
+        match message {
+            ServerMessage::Serverdata(data) => {
+                self.serverdata = data.clone().into_owned();
+            },
+            ServerMessage::Soundlist(data) => {
+                self.sounds.extend(data.sounds.iter().map(|s| s.clone().into_owned()));
+            }
+            ServerMessage::Modellist(data) => {
+                self.models.extend(data.models.iter().map(|s| s.clone().into_owned()));
+            }
+            ServerMessage::Spawnbaseline(data) => {
+                self.baseline_entities.insert(data.index, Entity::from_baseline(data));
+            }
+            ServerMessage::Spawnstatic(data) => {
+                self.static_entities.push(*data)
+            }
+            ServerMessage::Cdtrack(data) => {
+                self.cdtrack = data.track;
+            }
+            ServerMessage::Stufftext(data) => {
+                self.serverinfo.update_from_stufftext(&data.text.bytes);
+            },
+            ServerMessage::Spawnstaticsound(data) => {
+                self.static_sounds.push(data.clone());
+            }
+            ServerMessage::Updatefrags(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateping(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updatepl(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateentertime(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateuserinfo(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Playerinfo(data) => {
+                self.update_player(data.player_number() as u16, message)?;
+            }
+            ServerMessage::Updatestatlong(_) => {
+                self.update_player(last.to as u16, message)?;
+            }
+            ServerMessage::Updatestat(_) => {
+                self.update_player(last.to as u16, message)?;
+            }
+            ServerMessage::Setinfo(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Lightstyle(data) => {
+                self.lightstyles.insert(data.index, data.style.clone().into_owned());
+            }
+            ServerMessage::Serverinfo(data) => {
+                self.serverinfo.update_key_value(&data.key, &data.value);
+            }
+            ServerMessage::Centerprint(_) => {
+                // ignore
+            }
+            ServerMessage::Packetentities(data) => {
+                self.entity_sequence = self.entity_sequence.wrapping_add(1);
+                self.packet_entities(data);
+            }
+            ServerMessage::Deltapacketentities(data) => {
+                // mvds always delta from the previous frame, a demo that starts with a delta
+                // has nothing before it
+                self.entity_sequence = self.entity_sequence.wrapping_add(1);
+                if self.entity_frames.is_empty() {
+                    self.projectiles.clear();
+                    self.update_entities(None, &data.entities);
+                } else {
+                    self.deltapacket_entities(data, self.entity_sequence.wrapping_sub(1))?;
                 }
-                _ => { panic!("noooo! {:?} to: {}, type: {}", message, last.to, last.command)}
             }
+            ServerMessage::Tempentity(data) => {
+                self.temp_entities(data);
+            }
+            ServerMessage::Print(_) => {
+                // ignore
+            }
+            ServerMessage::Sound(_) => {
+                // maybe keep the sounds?
+            }
+            ServerMessage::Damage(_) => {
+                // ignore
+            }
+            ServerMessage::Setangle(data) => {
+                self.update_player(data.index as u16, message)?;
+            }
+            ServerMessage::Smallkick(_) => {
+                // ignore
+            }
+            ServerMessage::Muzzleflash(_) => {
+                // ignore
+            }
+            ServerMessage::Chokecount(_) => {
+                // ignore
+            }
+            ServerMessage::Bigkick(_) => {
+                // ignore
+            }
+            ServerMessage::Nails(data) => {
+                self.projectiles = data.projectiles.clone();
+            }
+            ServerMessage::Nails2(data) => {
+                self.projectiles = data.projectiles.clone();
+            }
+            ServerMessage::Intermission(_) => {
+                // ignore
+            }
+            ServerMessage::Disconnect(_) => {
+                // ignore
+            }
+            ServerMessage::Maxspeed(_) => {
+                self.update_player(last.to as u16, message)?;
+            }
+            ServerMessage::Entgravity(_) => {
+                self.update_player(last.to as u16, message)?;
+            }
+            ServerMessage::Setpause(data) => {
+                self.paused = data.paused != 0;
+            }
+            ServerMessage::Killedmonster(_) => {
+                self.killed_monsters += 1;
+            }
+            ServerMessage::Foundsecret(_) => {
+                self.found_secrets += 1;
+            }
+            ServerMessage::Stopsound(_) => {
+                // ignore
+            }
+            ServerMessage::Finale(_) => {
+                // ignore
+            }
+            ServerMessage::Sellscreen(_) => {
+                // ignore
+            }
+            ServerMessage::Download(_) => {
+                // ignore
+            }
+            _ => return Err(StateError::UnhandledMessage(message.to_string())),
         }
+        Ok(())
+    }
+
+    /// applies the messages of a server packet, its sequence is the one entity deltas refer to
+    pub fn apply_packet(&mut self, connected: &Connected) -> Result<(), StateError> {
+        // the high bit marks reliable data
+        self.set_incoming_sequence(connected.sequence & 0x7fffffff);
+        self.apply_messages(&connected.messages)
     }
 
-    pub fn apply_messages(&mut self, messages: &'_ Vec<ServerMessage>) {
+    pub fn apply_messages(&mut self, messages: &'_ Vec<ServerMessage>) -> Result<(), StateError> {
+        self.stat_changes.clear();
         for message in messages {
-            match message {
-                ServerMessage::Serverdata(data) => {
-                    self.serverdata = data.clone();
-                },
-                ServerMessage::Soundlist(data) => {
-                    self.sounds.extend(data.sounds.clone());
-                }
-                ServerMessage::Modellist(data) => {
-                    self.models.extend(data.models.clone());
-                }
-                ServerMessage::Spawnbaseline(data) => {
-                    self.baseline_entities.insert(data.index, Entity::from_baseline(data));
-                }
-                ServerMessage::Spawnstatic(data) => {
-                    self.static_entities.push(*data)
-                }
-                ServerMessage::Cdtrack(_) => {
-                    continue
-                }
-                ServerMessage::Stufftext(_) => {
-                    continue
-                },
-                ServerMessage::Spawnstaticsound(data) => {
-                    self.static_sounds.push(data.clone());
-                }
-                ServerMessage::Updatefrags(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateping(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updatepl(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateentertime(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updateuserinfo(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Playerinfo(_data) => {
-                    panic!("FIXME");
-                    //self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Updatestatlong(_) => {
-                //    self.update_player(last_to as u16, message);
-                }
-                ServerMessage::Updatestat(_) => {
-                //    self.update_player(last_to as u16, message);
-                }
-                ServerMessage::Setinfo(data) => {
-                    self.update_player(data.player_number as u16, message);
-                }
-                ServerMessage::Lightstyle(_) => {
-                    // ignore
-                }
-                ServerMessage::Serverinfo(_) => {
-                    // ignore, but probably shouldnt be
-                }
-                ServerMessage::Centerprint(_) => {
-                    // ignore
-                }
-                ServerMessage::Packetentities(data) => {
-                    self.packet_entities(data);
-                }
-                ServerMessage::Deltapacketentities(data) => {
-                    self.deltapacket_entities(data);
-                }
-                ServerMessage::Tempentity(data) => {
-                    self.temp_entities(data);
-                }
-                ServerMessage::Print(_) => {
-                    // ignore
-                }
-                ServerMessage::Sound(_) => {
-                    // maybe keep the sounds?
-                }
-                ServerMessage::Damage(_) => {
-                    // ignore
-                }
-                ServerMessage::Setangle(data) => {
-                    self.update_player(data.index as u16, message);
-                }
-                ServerMessage::Smallkick(_) => {
-                    // ignore
-                }
-                ServerMessage::Muzzleflash(_) => {
-                    // ignore
-                }
-                ServerMessage::Chokecount(_) => {
-                    // ignore
-                }
-                ServerMessage::Bigkick(_) => {
-                    // ignore
-                }
-                ServerMessage::Intermission(_) => {
-                    // ignore
-                }
-                ServerMessage::Disconnect(_) => {
-                    // ignore
-                }
-                _ => {}
+            let result = self.apply_message(message);
+            self.handle_error(result)?;
+        }
+        Ok(())
+    }
+
+    fn apply_message(&mut self, message: &ServerMessage) -> Result<(), StateError> {
+        match message {
+            ServerMessage::Serverdata(data) => {
+                self.serverdata = data.clone().into_owned();
+            },
+            ServerMessage::Soundlist(data) => {
+                self.sounds.extend(data.sounds.iter().map(|s| s.clone().into_owned()));
+            }
+            ServerMessage::Modellist(data) => {
+                self.models.extend(data.models.iter().map(|s| s.clone().into_owned()));
+            }
+            ServerMessage::Spawnbaseline(data) => {
+                self.baseline_entities.insert(data.index, Entity::from_baseline(data));
+            }
+            ServerMessage::Spawnstatic(data) => {
+                self.static_entities.push(*data)
+            }
+            ServerMessage::Cdtrack(data) => {
+                self.cdtrack = data.track;
+            }
+            ServerMessage::Stufftext(data) => {
+                self.serverinfo.update_from_stufftext(&data.text.bytes);
+            },
+            ServerMessage::Spawnstaticsound(data) => {
+                self.static_sounds.push(data.clone());
+            }
+            ServerMessage::Updatefrags(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateping(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updatepl(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateentertime(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Updateuserinfo(data) => {
+                self.update_player(data.player_number as u16, message)?;
             }
+            ServerMessage::Playerinfo(data) => {
+                self.update_player(data.player_number() as u16, message)?;
+            }
+            ServerMessage::Updatestatlong(_) => {
+                self.update_player(self.own_player_number(), message)?;
+            }
+            ServerMessage::Updatestat(_) => {
+                self.update_player(self.own_player_number(), message)?;
+            }
+            ServerMessage::Setinfo(data) => {
+                self.update_player(data.player_number as u16, message)?;
+            }
+            ServerMessage::Lightstyle(data) => {
+                self.lightstyles.insert(data.index, data.style.clone().into_owned());
+            }
+            ServerMessage::Serverinfo(data) => {
+                self.serverinfo.update_key_value(&data.key, &data.value);
+            }
+            ServerMessage::Centerprint(_) => {
+                // ignore
+            }
+            ServerMessage::Packetentities(data) => {
+                self.packet_entities(data);
+            }
+            ServerMessage::Deltapacketentities(data) => {
+                // only the lower 8 bits of the sequence are sent
+                let from = (self.entity_sequence & !0xff) | data.from as u32;
+                let from = if from > self.entity_sequence { from.wrapping_sub(256) } else { from };
+                self.deltapacket_entities(data, from)?;
+            }
+            ServerMessage::Tempentity(data) => {
+                self.temp_entities(data);
+            }
+            ServerMessage::Print(_) => {
+                // ignore
+            }
+            ServerMessage::Sound(_) => {
+                // maybe keep the sounds?
+            }
+            ServerMessage::Damage(_) => {
+                // ignore
+            }
+            ServerMessage::Setangle(data) => {
+                self.update_player(data.index as u16, message)?;
+            }
+            ServerMessage::Smallkick(_) => {
+                // ignore
+            }
+            ServerMessage::Muzzleflash(_) => {
+                // ignore
+            }
+            ServerMessage::Chokecount(_) => {
+                // ignore
+            }
+            ServerMessage::Bigkick(_) => {
+                // ignore
+            }
+            ServerMessage::Nails(data) => {
+                self.projectiles = data.projectiles.clone();
+            }
+            ServerMessage::Nails2(data) => {
+                self.projectiles = data.projectiles.clone();
+            }
+            ServerMessage::Intermission(_) => {
+                // ignore
+            }
+            ServerMessage::Disconnect(_) => {
+                // ignore
+            }
+            ServerMessage::Maxspeed(_) => {
+                self.update_player(self.own_player_number(), message)?;
+            }
+            ServerMessage::Entgravity(_) => {
+                self.update_player(self.own_player_number(), message)?;
+            }
+            ServerMessage::Setpause(data) => {
+                self.paused = data.paused != 0;
+            }
+            ServerMessage::Killedmonster(_) => {
+                self.killed_monsters += 1;
+            }
+            ServerMessage::Foundsecret(_) => {
+                self.found_secrets += 1;
+            }
+            ServerMessage::Stopsound(_) => {
+                // ignore
+            }
+            ServerMessage::Finale(_) => {
+                // ignore
+            }
+            ServerMessage::Sellscreen(_) => {
+                // ignore
+            }
+            ServerMessage::Download(_) => {
+                // ignore
+            }
+            _ => return Err(StateError::UnhandledMessage(message.to_string())),
         }
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::mvd::MvdTarget;
+    use crate::protocol::types::*;
+    use crate::state::{State, StatType};
+    use crate::state::errors::StateError;
+
+    #[test]
+    fn playerinfo() {
+        let mut state = State::new();
+        let target = MvdTarget{ to: 0, command: DemoCommand::All };
+        let playerinfo = |flags, origin, model| ServerMessage::Playerinfo(Playerinfo::PlayerinfoMvdT(PlayerinfoMvd{
+            player_number: 3,
+            flags,
+            frame: 7,
+            origin,
+            angle: None,
+            model,
+            skinnum: None,
+            effects: None,
+            weaponframe: None,
+        }));
+        state.apply_messages_mvd(&vec![playerinfo(DfTypes::ORIGIN | DfTypes::ORIGIN2 | DfTypes::MODEL,
+            Some(CoordinateVectorOption{ x: Some(10.0), y: Some(20.0), z: None }), Some(2))], target.clone()).unwrap();
+        // the delta only changes z
+        state.apply_messages_mvd(&vec![playerinfo(DfTypes::ORIGIN3 | DfTypes::DEAD,
+            Some(CoordinateVectorOption{ x: None, y: None, z: Some(-5.0) }), None)], target).unwrap();
+        let player = &state.players[&3];
+        assert_eq!(player.origin, CoordinateVector{ x: 10.0, y: 20.0, z: -5.0 });
+        assert_eq!(player.model, 2);
+        assert_eq!(player.frame, 7);
+        assert!(player.dead);
+
+        let mut command = DeltaUserCommand::default();
+        command.angle.y = Some(90.0);
+        state.apply_messages(&vec![ServerMessage::Playerinfo(Playerinfo::PlayerinfoConnectionT(PlayerinfoConnection{
+            player_number: 3,
+            flags: PFTypes::COMMAND | PFTypes::VELOCITY1,
+            origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 },
+            frame: 1,
+            msec: None,
+            command: Some(command),
+            velocity: VelocityVectorOption{ x: Some(320), y: None, z: None },
+            model: None,
+            skinnum: None,
+            effects: None,
+            weaponframe: None,
+            alpha: None,
+        }))]).unwrap();
+        let player = &state.players[&3];
+        assert_eq!(player.origin, CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 });
+        assert_eq!(player.angle.y, 90.0);
+        assert_eq!(player.velocity, VelocityVector{ x: 320, y: 0, z: 0 });
+        assert_eq!(player.model, 0);
+        assert!(!player.dead);
+        assert!(player.command.is_some());
+    }
+
+    fn delta(entity_index: u16, origin: Option<CoordinateVectorOption>, remove: bool) -> Packetentity {
+        Packetentity{ entity_index, origin, remove, ..Default::default() }
+    }
+
+    #[test]
+    fn entity_frames() {
+        let mut state = State::new();
+        let target = MvdTarget{ to: 0, command: DemoCommand::All };
+        state.apply_messages_mvd(&vec![ServerMessage::Spawnbaseline(Spawnbaseline{
+            index: 5,
+            model_index: 3,
+            model_frame: 0,
+            colormap: 0,
+            skinnum: 1,
+            origin: CoordinateVector{ x: 1.0, y: 2.0, z: 3.0 },
+            angle: AngleVector::default(),
+        })], target.clone()).unwrap();
+        state.apply_messages_mvd(&vec![ServerMessage::Packetentities(Packetentities{ entities: vec![
+            delta(5, Some(CoordinateVectorOption{ x: Some(10.0), y: None, z: None }), false),
+        ]})], target.clone()).unwrap();
+        assert_eq!(state.entities[&5].origin, CoordinateVector{ x: 10.0, y: 2.0, z: 3.0 });
+        assert_eq!(state.entities[&5].model, 3);
+        assert_eq!(state.entities[&5].skinnum, 1);
+
+        state.apply_messages_mvd(&vec![ServerMessage::Deltapacketentities(Deltapacketentities{ from: 0, entities: vec![
+            delta(7, None, false),
+            delta(5, Some(CoordinateVectorOption{ x: None, y: Some(20.0), z: None }), false),
+        ]})], target.clone()).unwrap();
+        assert_eq!(state.entities[&5].origin, CoordinateVector{ x: 10.0, y: 20.0, z: 3.0 });
+        assert!(state.entities.contains_key(&7));
+
+        state.apply_messages_mvd(&vec![ServerMessage::Deltapacketentities(Deltapacketentities{ from: 0, entities: vec![
+            delta(5, None, true),
+        ]})], target).unwrap();
+        assert!(!state.entities.contains_key(&5));
+        assert!(state.entities.contains_key(&7));
+
+        // connection deltas reference the frame by sequence
+        let mut state = State::new();
+        state.set_incoming_sequence(300);
+        state.apply_messages(&vec![ServerMessage::Packetentities(Packetentities{ entities: vec![delta(5, None, false)] })]).unwrap();
+        state.set_incoming_sequence(301);
+        state.apply_messages(&vec![ServerMessage::Deltapacketentities(Deltapacketentities{ from: (300 & 0xff) as u8, entities: vec![delta(6, None, false)] })]).unwrap();
+        assert_eq!(state.entities.len(), 2);
+        state.set_incoming_sequence(302);
+        assert_eq!(state.apply_messages(&vec![ServerMessage::Deltapacketentities(Deltapacketentities{ from: 7, entities: vec![delta(8, None, false)] })]),
+            Err(StateError::MissingDeltaFrame(263)));
+        assert_eq!(state.entities.len(), 2);
+
+        // the sequence is taken from the packet
+        let mut state = State::new();
+        let packet = |sequence, message| Connected{ sequence, sequence_ack: 0, messages: vec![message] };
+        state.apply_packet(&packet(0x80000000 | 510, ServerMessage::Packetentities(Packetentities{ entities: vec![delta(5, None, false)] }))).unwrap();
+        state.apply_packet(&packet(512, ServerMessage::Deltapacketentities(Deltapacketentities{ from: (510 & 0xff) as u8, entities: vec![delta(6, None, false)] }))).unwrap();
+        assert_eq!(state.entity_sequence, 512);
+        assert_eq!(state.entities.len(), 2);
+    }
+
+    #[test]
+    fn projectiles() {
+        let projectile = |x| Projectile{ number: None, origin: CoordinateVector{ x, y: 0.0, z: 0.0 }, angle: AngleVector::default() };
+        let nails = ServerMessage::Nails(Nails{ projectiles: vec![projectile(10.0), projectile(20.0)] });
+        let nails2 = ServerMessage::Nails2(Nails2{ projectiles: vec![Projectile{ number: Some(3), ..projectile(30.0) }] });
+        let packet_entities = ServerMessage::Packetentities(Packetentities{ entities: vec![delta(5, None, false)] });
+        let deltapacket_entities = ServerMessage::Deltapacketentities(Deltapacketentities{ from: 0, entities: vec![] });
+
+        let mut state = State::new();
+        let target = MvdTarget{ to: 0, command: DemoCommand::All };
+        state.apply_messages_mvd(&vec![packet_entities.clone(), nails], target.clone()).unwrap();
+        assert_eq!(state.projectiles.len(), 2);
+        assert_eq!(state.projectiles[1].origin.x, 20.0);
+        state.apply_messages_mvd(&vec![packet_entities.clone()], target.clone()).unwrap();
+        assert!(state.projectiles.is_empty());
+
+        state.apply_messages_mvd(&vec![nails2.clone()], target.clone()).unwrap();
+        assert_eq!(state.projectiles[0].number, Some(3));
+        state.apply_messages_mvd(&vec![deltapacket_entities], target).unwrap();
+        assert!(state.projectiles.is_empty());
+
+        let mut state = State::new();
+        state.set_incoming_sequence(1);
+        state.apply_messages(&vec![packet_entities, nails2]).unwrap();
+        assert_eq!(state.projectiles.len(), 1);
+        state.set_incoming_sequence(2);
+        state.apply_messages(&vec![ServerMessage::Deltapacketentities(Deltapacketentities{ from: 1, entities: vec![] })]).unwrap();
+        assert!(state.projectiles.is_empty());
+    }
+
+    #[test]
+    fn connection_stats() {
+        let mut state = State::new();
+        state.apply_messages(&vec![
+            ServerMessage::Serverdata(Serverdata{ player_number: 0x80 | 4, ..Default::default() }),
+            ServerMessage::Updatestat(Updatestat{ stat: StatType::Health as u8, value: 100 }),
+            ServerMessage::Updatestatlong(Updatestatlong{ stat: StatType::Frags as u8, value: 3 }),
+        ]).unwrap();
+        assert_eq!(state.players[&4].health(), 100);
+        assert_eq!(state.players[&4].stat_frags(), 3);
+        assert_eq!(state.stat_changes.len(), 2);
+
+        // unknown messages are errors on both paths
+        let unhandled = vec![ServerMessage::Setview(Setview{ setview: 1 })];
+        assert!(matches!(state.apply_messages(&unhandled), Err(StateError::UnhandledMessage(_))));
+        assert!(matches!(state.apply_messages_mvd(&unhandled, MvdTarget::default()), Err(StateError::UnhandledMessage(_))));
+        state.lenient = true;
+        state.apply_messages(&unhandled).unwrap();
+        assert_eq!(state.warnings.len(), 1);
+    }
+
+    #[test]
+    fn game_messages() {
+        let messages = vec![
+            ServerMessage::Setpause(Setpause{ paused: 1 }),
+            ServerMessage::Killedmonster(Killedmonster{}),
+            ServerMessage::Killedmonster(Killedmonster{}),
+            ServerMessage::Foundsecret(Foundsecret{}),
+            ServerMessage::Maxspeed(Maxspeed{ maxspeed: 400.0 }),
+            ServerMessage::Entgravity(Entgravity{ gravity: 0.5 }),
+        ];
+        let mut mvd = State::new();
+        mvd.apply_messages_mvd(&messages, MvdTarget{ to: 2, command: DemoCommand::Single }).unwrap();
+        let mut connection = State::new();
+        connection.apply_messages(&vec![ServerMessage::Serverdata(Serverdata{ player_number: 2, ..Default::default() })]).unwrap();
+        connection.apply_messages(&messages).unwrap();
+        for state in [&mvd, &connection] {
+            assert!(state.paused);
+            assert_eq!(state.killed_monsters, 2);
+            assert_eq!(state.found_secrets, 1);
+            assert_eq!(state.players[&2].maxspeed, Some(400.0));
+            assert_eq!(state.players[&2].entgravity, Some(0.5));
+        }
+
+        mvd.apply_messages_mvd(&vec![ServerMessage::Setpause(Setpause{ paused: 0 })], MvdTarget::default()).unwrap();
+        assert!(!mvd.paused);
+    }
+
+    #[test]
+    fn lenient() {
+        let target = MvdTarget{ to: 2, command: DemoCommand::Single };
+        let messages = vec![
+            ServerMessage::Updatestat(Updatestat{ stat: 200, value: 1 }),
+            ServerMessage::Updatestat(Updatestat{ stat: 0, value: 100 }),
+        ];
+        let mut state = State::new();
+        assert_eq!(state.apply_messages_mvd(&messages, target.clone()), Err(StateError::StatOutOfRange(200)));
+
+        let mut state = State::new();
+        state.lenient = true;
+        state.apply_messages_mvd(&messages, target).unwrap();
+        assert_eq!(state.warnings, vec![StateError::StatOutOfRange(200)]);
+        assert_eq!(state.players[&2].stats[0], 100);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use crate::protocol::types::StringByte;
+use crate::utils::userinfo::Userinfo;
+
+/// the serverinfo keys that are set by most servers
+///
+/// updated from [`crate::protocol::types::ServerMessage::Serverinfo`] and the `fullserverinfo`
+/// stufftext, all keys are kept in `info`
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ServerInfo {
+    pub info: Userinfo,
+    pub hostname: StringByte<'static>,
+    pub map: StringByte<'static>,
+    /// `*gamedir`
+    pub gamedir: StringByte<'static>,
+    pub deathmatch: u8,
+    pub teamplay: u8,
+    pub timelimit: u32,
+    pub fraglimit: u32,
+    pub maxclients: u8,
+    pub maxspectators: u8,
+    pub maxfps: u32,
+    /// set by ktx, e.g. "Standby" or "3 min left"
+    pub status: StringByte<'static>,
+    /// set by ktx, e.g. "duel" or "team"
+    pub mode: StringByte<'static>,
+    pub ktxver: StringByte<'static>,
+    /// `*version`
+    pub version: StringByte<'static>,
+}
+
+fn number<T: std::str::FromStr + Default>(value: &StringByte) -> T {
+    String::from_utf8_lossy(&value.bytes).trim().parse().unwrap_or_default()
+}
+
+impl ServerInfo {
+    pub fn get(&self, key: &str) -> Option<&StringByte<'static>> {
+        self.info.values.iter()
+            .find(|(k, _)| k.bytes == key.as_bytes())
+            .map(|(_, v)| v)
+    }
+
+    pub fn update_key_value(&mut self, key: &StringByte, value: &StringByte) {
+        self.info.update_key_value(key, value);
+        self.update_field(key, value);
+    }
+
+    /// removes `key` and resets the field it was read into
+    pub fn remove_key(&mut self, key: &StringByte) {
+        if self.info.remove_key(key) {
+            self.update_field(key, &StringByte::default());
+        }
+    }
+
+    /// replaces all keys with the ones of a `\key\value` string
+    pub fn update(&mut self, serverinfo: &[u8]) {
+        *self = ServerInfo::default();
+        self.info.update(&StringByte{ bytes: serverinfo.into(), ..Default::default() });
+        for (key, value) in self.info.values.clone() {
+            self.update_field(&key, &value);
+        }
+    }
+
+    /// applies a `fullserverinfo "\key\value"` stufftext, returns false for other commands
+    pub fn update_from_stufftext(&mut self, text: &[u8]) -> bool {
+        let mut updated = false;
+        for line in text.split(|b| *b == b'\n') {
+            if let Some(serverinfo) = line.strip_prefix(b"fullserverinfo ") {
+                let serverinfo: Vec<u8> = serverinfo.iter().copied().filter(|b| *b != b'"').collect();
+                self.update(&serverinfo);
+                updated = true;
+            }
+        }
+        updated
+    }
+
+    fn update_field(&mut self, key: &StringByte, value: &StringByte) {
+        match key.bytes.as_ref() {
+            b"hostname" => self.hostname = value.clone().into_owned(),
+            b"map" => self.map = value.clone().into_owned(),
+            b"*gamedir" => self.gamedir = value.clone().into_owned(),
+            b"deathmatch" => self.deathmatch = number(value),
+            b"teamplay" => self.teamplay = number(value),
+            b"timelimit" => self.timelimit = number(value),
+            b"fraglimit" => self.fraglimit = number(value),
+            b"maxclients" => self.maxclients = number(value),
+            b"maxspectators" => self.maxspectators = number(value),
+            b"maxfps" => self.maxfps = number(value),
+            b"status" => self.status = value.clone().into_owned(),
+            b"mode" => self.mode = value.clone().into_owned(),
+            b"ktxver" => self.ktxver = value.clone().into_owned(),
+            b"*version" => self.version = value.clone().into_owned(),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::MvdTarget;
+    use crate::protocol::types::*;
+    use crate::state::State;
+    use crate::utils::ascii_converter::AsciiConverter;
+
+    #[test]
+    fn serverinfo() {
+        let ascii_converter = AsciiConverter::new();
+        let mut state = State::new();
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Stufftext(Stufftext{ text: ascii_converter.convert_to_stringbyte("fullserverinfo \"\\hostname\\qw.example.com\\teamplay\\2\\timelimit\\20\\*version\\MVDSV 0.36\"\n") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("status"), value: ascii_converter.convert_to_stringbyte("Standby") }),
+            ServerMessage::Serverinfo(Serverinfo{ key: ascii_converter.convert_to_stringbyte("timelimit"), value: ascii_converter.convert_to_stringbyte("10") }),
+        ], MvdTarget{ to: 0, command: DemoCommand::All }).unwrap();
+        let serverinfo = &state.serverinfo;
+        assert_eq!(serverinfo.hostname.bytes.as_ref(), b"qw.example.com");
+        assert_eq!(serverinfo.teamplay, 2);
+        assert_eq!(serverinfo.timelimit, 10);
+        assert_eq!(serverinfo.version.bytes.as_ref(), b"MVDSV 0.36");
+        assert_eq!(serverinfo.status.bytes.as_ref(), b"Standby");
+        assert_eq!(serverinfo.get("timelimit").unwrap().bytes.as_ref(), b"10");
+        assert_eq!(serverinfo.info.values.len(), 5);
+    }
+}
@@ -0,0 +1,207 @@
+use num_enum::TryFromPrimitive;
+use strum_macros::Display;
+use bitflags::bitflags;
+use serde::Serialize;
+use crate::state::Player;
+
+/// indices into [`Player::stats`]
+#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, TryFromPrimitive, Display, Copy, Clone, Serialize)]
+#[repr(u8)]
+pub enum StatType {
+    Health = 0,
+    Frags,
+    Weapon,
+    Ammo,
+    Armor,
+    Weaponframe,
+    Shells,
+    Nails,
+    Rockets,
+    Cells,
+    Activeweapon,
+    Totalsecrets,
+    Totalmonsters,
+    Secrets,
+    Monsters,
+    Items,
+    Viewheight,
+    Time,
+}
+
+bitflags! {
+#[derive(Serialize, Default)]
+    pub struct Items: u32 {
+        const SHOTGUN = 1;
+        const SUPER_SHOTGUN = 1 << 1;
+        const NAILGUN = 1 << 2;
+        const SUPER_NAILGUN = 1 << 3;
+        const GRENADE_LAUNCHER = 1 << 4;
+        const ROCKET_LAUNCHER = 1 << 5;
+        const LIGHTNING = 1 << 6;
+        const SUPER_LIGHTNING = 1 << 7;
+        const SHELLS = 1 << 8;
+        const NAILS = 1 << 9;
+        const ROCKETS = 1 << 10;
+        const CELLS = 1 << 11;
+        const AXE = 1 << 12;
+        const ARMOR1 = 1 << 13;
+        const ARMOR2 = 1 << 14;
+        const ARMOR3 = 1 << 15;
+        const SUPERHEALTH = 1 << 16;
+        const KEY1 = 1 << 17;
+        const KEY2 = 1 << 18;
+        const INVISIBILITY = 1 << 19;
+        const INVULNERABILITY = 1 << 20;
+        const SUIT = 1 << 21;
+        const QUAD = 1 << 22;
+        const SIGIL1 = 1 << 28;
+        const SIGIL2 = 1 << 29;
+        const SIGIL3 = 1 << 30;
+        const SIGIL4 = 1 << 31;
+    }
+}
+
+/// a stat of a player that changed, collected in [`crate::state::State::stat_changes`]
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StatChange {
+    pub player_number: u16,
+    pub stat: u8,
+    pub from: i32,
+    pub to: i32,
+}
+
+impl StatChange {
+    pub fn stat_type(&self) -> Option<StatType> {
+        StatType::try_from(self.stat).ok()
+    }
+
+    /// items that were picked up, only set for [`StatType::Items`]
+    pub fn items_gained(&self) -> Items {
+        match self.stat_type() {
+            Some(StatType::Items) => Items::from_bits_truncate(self.to as u32) - Items::from_bits_truncate(self.from as u32),
+            _ => Items::empty(),
+        }
+    }
+
+    /// items that were lost or used up, only set for [`StatType::Items`]
+    pub fn items_lost(&self) -> Items {
+        match self.stat_type() {
+            Some(StatType::Items) => Items::from_bits_truncate(self.from as u32) - Items::from_bits_truncate(self.to as u32),
+            _ => Items::empty(),
+        }
+    }
+}
+
+impl Player {
+    pub fn stat(&self, stat: StatType) -> i32 {
+        self.stats[stat as usize]
+    }
+
+    pub fn health(&self) -> i32 {
+        self.stat(StatType::Health)
+    }
+
+    /// the frags stat, [`Player::frags`] is the one from the scoreboard
+    pub fn stat_frags(&self) -> i32 {
+        self.stat(StatType::Frags)
+    }
+
+    pub fn weapon(&self) -> i32 {
+        self.stat(StatType::Weapon)
+    }
+
+    /// ammo of the active weapon
+    pub fn ammo(&self) -> i32 {
+        self.stat(StatType::Ammo)
+    }
+
+    pub fn armor(&self) -> i32 {
+        self.stat(StatType::Armor)
+    }
+
+    /// the weaponframe stat, [`Player::weaponframe`] is the one from the playerinfo
+    pub fn stat_weaponframe(&self) -> i32 {
+        self.stat(StatType::Weaponframe)
+    }
+
+    pub fn shells(&self) -> i32 {
+        self.stat(StatType::Shells)
+    }
+
+    pub fn nails(&self) -> i32 {
+        self.stat(StatType::Nails)
+    }
+
+    pub fn rockets(&self) -> i32 {
+        self.stat(StatType::Rockets)
+    }
+
+    pub fn cells(&self) -> i32 {
+        self.stat(StatType::Cells)
+    }
+
+    /// the [`Items`] bit of the active weapon
+    pub fn active_weapon(&self) -> Items {
+        Items::from_bits_truncate(self.stat(StatType::Activeweapon) as u32)
+    }
+
+    pub fn total_secrets(&self) -> i32 {
+        self.stat(StatType::Totalsecrets)
+    }
+
+    pub fn total_monsters(&self) -> i32 {
+        self.stat(StatType::Totalmonsters)
+    }
+
+    pub fn secrets(&self) -> i32 {
+        self.stat(StatType::Secrets)
+    }
+
+    pub fn monsters(&self) -> i32 {
+        self.stat(StatType::Monsters)
+    }
+
+    pub fn items(&self) -> Items {
+        Items::from_bits_truncate(self.stat(StatType::Items) as u32)
+    }
+
+    pub fn viewheight(&self) -> i32 {
+        self.stat(StatType::Viewheight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mvd::MvdTarget;
+    use crate::protocol::types::*;
+    use crate::state::{Items, State, StatType};
+
+    #[test]
+    fn stats() {
+        let mut state = State::new();
+        let target = MvdTarget{ to: 4, command: DemoCommand::Stats };
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Updatestat(Updatestat{ stat: StatType::Health as u8, value: 100 }),
+            ServerMessage::Updatestatlong(Updatestatlong{ stat: StatType::Items as u8, value: (Items::SHOTGUN | Items::AXE | Items::SIGIL1).bits() as i32 }),
+        ], target.clone()).unwrap();
+        assert_eq!(state.stat_changes.len(), 2);
+
+        state.apply_messages_mvd(&vec![
+            ServerMessage::Updatestat(Updatestat{ stat: StatType::Health as u8, value: 100 }),
+            ServerMessage::Updatestat(Updatestat{ stat: StatType::Frags as u8, value: 7 }),
+            ServerMessage::Updatestat(Updatestat{ stat: StatType::Weaponframe as u8, value: 3 }),
+            ServerMessage::Updatestatlong(Updatestatlong{ stat: StatType::Items as u8, value: (Items::SHOTGUN | Items::ROCKET_LAUNCHER | Items::QUAD | Items::SIGIL1).bits() as i32 }),
+        ], target).unwrap();
+        let player = &state.players[&4];
+        assert_eq!(player.health(), 100);
+        assert_eq!(player.stat_frags(), 7);
+        assert_eq!(player.stat_weaponframe(), 3);
+        assert!(player.items().contains(Items::QUAD | Items::SIGIL1));
+        // unchanged values are not reported
+        assert_eq!(state.stat_changes.len(), 3);
+        let change = state.stat_changes[2];
+        assert_eq!(change.stat_type(), Some(StatType::Items));
+        assert_eq!(change.items_gained(), Items::ROCKET_LAUNCHER | Items::QUAD);
+        assert_eq!(change.items_lost(), Items::AXE);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(not(feature = "ascii_strings"))]
+use std::borrow::Cow;
 use std::error::Error;
 use serde::Serialize;
 use simple_error::bail;
@@ -56,14 +58,14 @@ impl AsciiConverter {
     }
 
 #[cfg(feature = "ascii_strings")]
-    pub fn convert_to_stringbyte(&self, bytes: impl Into<Vec<u8>>) -> StringByte {
+    pub fn convert_to_stringbyte(&self, bytes: impl Into<Vec<u8>>) -> StringByte<'static> {
         let bytes = bytes.into();
         StringByte::new(bytes.to_vec(), self)
     }
 #[cfg(not(feature = "ascii_strings"))]
-    pub fn convert_to_stringbyte(&self, bytes: impl Into<Vec<u8>>) -> StringByte {
+    pub fn convert_to_stringbyte(&self, bytes: impl Into<Vec<u8>>) -> StringByte<'static> {
         let bytes = bytes.into();
-        StringByte{ bytes }
+        StringByte{ bytes: Cow::Owned(bytes) }
     }
 
 }
@@ -1,4 +1,5 @@
 
+use std::borrow::Cow;
 use serde::Serialize;
 
 #[cfg(feature = "ascii_strings")]
@@ -10,7 +11,7 @@ use crate::protocol::types::StringByte;
 pub struct Userinfo {
 #[cfg(feature = "ascii_strings")]
     ascii_converter: AsciiConverter,
-    pub values: Vec<(StringByte, StringByte)>,
+    pub values: Vec<(StringByte<'static>, StringByte<'static>)>,
 }
 
 impl Userinfo {
@@ -30,11 +31,11 @@ impl Userinfo {
         for i in 0..self.values.len() {
             let (k, _) = &self.values[i];
             if k.string == key.string {
-                self.values[i] = (key.clone(), value.clone());
+                self.values[i] = (key.clone().into_owned(), value.clone().into_owned());
                 return
             }
         }
-        self.values.push((key.clone(), value.clone()));
+        self.values.push((key.clone().into_owned(), value.clone().into_owned()));
     }
 
 #[cfg(not(feature = "ascii_strings"))]
@@ -42,14 +43,21 @@ impl Userinfo {
         for i in 0..self.values.len() {
             let (k, _) = &self.values[i];
             if *k == *key {
-                self.values[i] = (key.clone(), value.clone());
+                self.values[i] = (key.clone().into_owned(), value.clone().into_owned());
                 return
             }
         }
-        self.values.push((key.clone(), value.clone()));
+        self.values.push((key.clone().into_owned(), value.clone().into_owned()));
     }
 
+    /// removes `key`, returns false if it wasn't set
+    pub fn remove_key(&mut self, key: &StringByte) -> bool {
+        let len = self.values.len();
+        self.values.retain(|(k, _)| k.bytes != key.bytes);
+        len != self.values.len()
+    }
 
+    /// adds the keys of a `\key\value` string, keys that are already set are replaced
 #[cfg(feature = "ascii_strings")]
     pub fn update(&mut self, userinfo: &StringByte) {
         let mut start = true;
@@ -67,11 +75,11 @@ impl Userinfo {
                     } else {
                         let sb_k = StringByte{
                                     string: self.ascii_converter.convert(key_vec.clone()),
-                                    bytes: key_vec.clone()};
+                                    bytes: Cow::Owned(key_vec.clone())};
                         let sb_v = StringByte{
                                     string: self.ascii_converter.convert(v.clone()),
-                                    bytes: v.clone()};
-                        self.values.push((sb_k ,sb_v))
+                                    bytes: Cow::Owned(v.clone())};
+                        self.update_key_value(&sb_k, &sb_v);
                     }
                     key = !key;
                     v.clear();
@@ -80,8 +88,19 @@ impl Userinfo {
             }
             v.push(userinfo.bytes[i]);
         }
+        // the last value isn't followed by a backslash
+        if !key {
+            let sb_k = StringByte{
+                        string: self.ascii_converter.convert(key_vec.clone()),
+                        bytes: Cow::Owned(key_vec)};
+            let sb_v = StringByte{
+                        string: self.ascii_converter.convert(v.clone()),
+                        bytes: Cow::Owned(v)};
+            self.update_key_value(&sb_k, &sb_v);
+        }
     }
 
+    /// adds the keys of a `\key\value` string, keys that are already set are replaced
 #[cfg(not(feature = "ascii_strings"))]
     pub fn update(&mut self, userinfo: &StringByte) {
         let mut start = true;
@@ -97,10 +116,10 @@ impl Userinfo {
                     if key {
                         key_vec = v.clone();
                     } else {
-                        self.values.push((
-                                    StringByte{ bytes: key_vec.clone()},
-                                    StringByte{ bytes: v.clone()},
-                                    ))
+                        self.update_key_value(
+                                    &StringByte{ bytes: Cow::Owned(key_vec.clone())},
+                                    &StringByte{ bytes: Cow::Owned(v.clone())},
+                                    );
                     }
                     key = !key;
                     v.clear();
@@ -109,6 +128,13 @@ impl Userinfo {
             }
             v.push(userinfo.bytes[i]);
         }
+        // the last value isn't followed by a backslash
+        if !key {
+            self.update_key_value(
+                        &StringByte{ bytes: Cow::Owned(key_vec)},
+                        &StringByte{ bytes: Cow::Owned(v)},
+                        );
+        }
     }
 
 #[cfg(not(feature = "ascii_strings"))]
@@ -136,18 +162,41 @@ impl Userinfo {
 #[cfg(feature = "ascii_strings")]
             {
                 rb.push(b'\\');
-                rb.extend(k.bytes.clone());
+                rb.extend(k.bytes.iter());
                 rb.push(b'\\');
-                rb.extend(v.bytes.clone());
+                rb.extend(v.bytes.iter());
             }
 #[cfg(not(feature = "ascii_strings"))]
             {
                 rb.push(b'\\');
-                rb.extend(k.bytes.clone());
+                rb.extend(k.bytes.iter());
                 rb.push(b'\\');
-                rb.extend(v.bytes.clone());
+                rb.extend(v.bytes.iter());
             }
         }
         rb
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::ascii_converter::AsciiConverter;
+    use crate::utils::userinfo::Userinfo;
+
+    #[test]
+    fn update() {
+        let ascii_converter = AsciiConverter::new();
+        let mut userinfo = Userinfo::new();
+        userinfo.update(&ascii_converter.convert_to_stringbyte("\\name\\alice\\team\\red"));
+        assert_eq!(userinfo.values.len(), 2);
+        let (key, value) = &userinfo.values[1];
+        assert_eq!(key.bytes.as_ref(), b"team");
+        assert_eq!(value.bytes.as_ref(), b"red");
+        assert_eq!(userinfo.as_bytes(), b"\\name\\alice\\team\\red");
+
+        // keys that are already set are replaced
+        userinfo.update(&ascii_converter.convert_to_stringbyte("\\team\\blue\\skin\\base"));
+        assert_eq!(userinfo.values.len(), 3);
+        assert_eq!(userinfo.as_bytes(), b"\\name\\alice\\team\\blue\\skin\\base");
+    }
+}